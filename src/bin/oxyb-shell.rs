@@ -0,0 +1,236 @@
+//! The terminal without the game: runs the same shell, commands and filesystem against stdin and stdout,
+//! one command per line, so shell behaviour can be scripted and its output diffed.
+//!
+//!     cargo run --bin oxyb-shell < session.txt > session.out
+//!     cargo run --bin oxyb-shell -- --fs assets/terminal/default.vfs.json
+//!
+//! Nothing is loaded from or saved to the player's data directory.
+//! Time only passes while a foreground process runs, so background jobs still running at the end of input are dropped.
+//! The exit status is that of the last command, as in a shell script
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+use bevy::prelude::*;
+use oxyb::commands::{CommandRegistry, CommandsPlugin, TerminalAction};
+use oxyb::console::{self, CurrentDirectory, Directory, GameDirectory, LineContext};
+use oxyb::history::{self, CommandHistory};
+use oxyb::process::{self, ProcessTable};
+use oxyb::programs::{ProgramRegistry, ProgramsPlugin};
+use oxyb::render::{self, TermColor};
+use oxyb::shell::ShellEnv;
+use oxyb::users::{Session, Users};
+use oxyb::vfs;
+
+
+
+const USAGE: &str = "usage: oxyb-shell [--fs definition.vfs.json] [--color]
+
+  --fs PATH   build the filesystem from a definition file instead of the built in one
+  --color     keep the terminal's colours as escape codes instead of printing plain text";
+
+// processes are ticked in steps of simulated time, so sleeps and downloads finish without waiting on the clock
+const TICK: f32 = 0.1;
+
+// a foreground process still running after this many simulated seconds is stopped as if by ctrl+c
+const TIME_LIMIT: f32 = 3600.0;
+
+struct Options {
+    definition: Option<String>,
+    color: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options { definition: None, color: false };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fs" => { options.definition = Some(args.next().ok_or("--fs needs a path")?); }
+            "--color" => { options.color = true; }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => { return Err(format!("unknown argument '{}'", arg)); }
+        }
+    }
+    Ok(options)
+}
+
+fn load_definition(path: Option<&str>) -> Result<Directory, String> {
+    match path {
+        Some(path) => {
+            let bytes = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+            vfs::parse_definition(&bytes).map_err(|err| format!("{}: {}", path, err))
+        }
+        None => Ok(vfs::built_in_definition()),
+    }
+}
+
+
+
+//                                                    SHELL
+
+/// What a console in the game keeps between commands, without its screen
+struct Shell {
+    root: GameDirectory,
+    current_directory: CurrentDirectory,
+    session: Session,
+    users: Users,
+    history: CommandHistory, // kept in memory, the player's saved histories are left alone
+    env: ShellEnv,
+    registry: CommandRegistry,
+    programs: ProgramRegistry,
+    processes: ProcessTable,
+    color: bool,
+}
+
+impl Shell {
+    fn new(definition: Directory, color: bool) -> Self {
+        // the plugins only register commands and programs, no window or renderer is started
+        let mut app = App::new();
+        app.add_plugins((CommandsPlugin, ProgramsPlugin));
+
+        Shell {
+            root: GameDirectory::new(definition),
+            current_directory: CurrentDirectory::default(),
+            session: Session::default(),
+            users: Users::default(),
+            history: CommandHistory::default(),
+            env: ShellEnv::default(),
+            registry: app.world.remove_resource::<CommandRegistry>().expect("CommandsPlugin adds the registry"),
+            programs: app.world.remove_resource::<ProgramRegistry>().expect("ProgramsPlugin adds the registry"),
+            processes: ProcessTable::default(),
+            color,
+        }
+    }
+
+    fn print(&self, lines: impl IntoIterator<Item = String>) {
+        let mut stdout = io::stdout().lock();
+        for line in lines {
+            let line = if self.color { line } else { render::strip_styles(&line) };
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+
+    fn print_error(&self, message: impl Into<String>) {
+        self.print([render::paint(TermColor::Red, format!("! {}", message.into()))]);
+    }
+
+    /// The prompt the game would show right now
+    fn prompt(&self) -> String {
+        match &self.session.prompt {
+            Some(prompt) => prompt.label(),
+            None => String::from(if self.session.is_root() { "# " } else { "> " }),
+        }
+    }
+
+    /// Runs a line as if it had been typed at the prompt and enter pressed. Returns false once the shell has exited
+    fn run_line(&mut self, line: &str) -> bool {
+        // there is no game here to react to the commands that ran
+        let (output, _) = console::run_line(line, LineContext {
+            fs: &mut self.root.fs,
+            current_directory: &mut self.current_directory,
+            history: &mut self.history,
+            session: &mut self.session,
+            env: &mut self.env,
+            processes: &mut self.processes,
+            users: &self.users,
+            registry: &self.registry,
+            programs: &self.programs,
+        });
+        self.print(output.lines);
+        self.print(output.errors);
+
+        let mut running = true;
+        for action in output.actions {
+            match action {
+                TerminalAction::Clear => {}
+                TerminalAction::Exit => { running = false; }
+                TerminalAction::Page(_, lines) => { self.print(lines); }
+                TerminalAction::Edit(_) => { self.print_error("edit: the editor needs the game window"); }
+            }
+        }
+
+        self.wait_for_foreground();
+        running
+    }
+
+    /// Answers the waiting password prompt with a line of input
+    fn answer_prompt(&mut self, line: String) {
+        if let Some(prompt) = &mut self.session.prompt {
+            prompt.input = line;
+        }
+        match self.session.submit_password(&self.users) {
+            Ok(user) => self.print([format!("logged in as {}", user)]),
+            Err(err) => self.print_error(err),
+        }
+    }
+
+    /// Ticks processes until the foreground one exits, as the game hides the prompt until then
+    fn wait_for_foreground(&mut self) {
+        let mut waited = 0.0;
+        while let Some(pid) = self.processes.foreground() {
+            if waited >= TIME_LIMIT {
+                self.processes.kill(pid);
+                self.print([String::from("^C")]);
+                self.env.last_status = process::INTERRUPTED;
+                return;
+            }
+
+            let output = self.processes.tick(TICK, &mut self.root.fs);
+            self.print(output.lines);
+            if let Some(code) = output.status {
+                self.env.last_status = code;
+            }
+            waited += TICK;
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("oxyb-shell: {}\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let definition = match load_definition(options.definition.as_deref()) {
+        Ok(definition) => definition,
+        Err(err) => {
+            eprintln!("oxyb-shell: {}", err);
+            return ExitCode::from(2);
+        }
+    };
+    let mut shell = Shell::new(definition, options.color);
+
+    // typed sessions get a prompt, piped ones get each line echoed the way the game's terminal shows it
+    let interactive = io::stdin().is_terminal();
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let prompt = shell.prompt();
+        if interactive {
+            print!("{}", prompt);
+            let _ = io::stdout().flush();
+        }
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => { break; }
+        };
+
+        if shell.session.prompt.is_some() {
+            // the password itself is never shown
+            if !interactive { shell.print([prompt]); }
+            shell.answer_prompt(line);
+            continue;
+        }
+
+        if !interactive { shell.print([format!("$ {}", history::mask_passwords(&line))]); }
+        if !shell.run_line(&line) {
+            break;
+        }
+    }
+
+    ExitCode::from(shell.env.last_status.clamp(0, 255) as u8)
+}
@@ -29,6 +29,10 @@ impl Default for MovementSettings {
     }
 }
 
+/// While set, mouse motion does not turn the camera, for when something else is pointing it
+#[derive(Resource, Default)]
+pub struct LookLocked(pub bool);
+
 /// Key configuration
 #[derive(Resource)]
 pub struct KeyBindings {
@@ -148,7 +152,13 @@ fn player_look(
     mut state: ResMut<InputState>,
     motion: Res<Events<MouseMotion>>,
     mut query: Query<&mut Transform, With<FlyCam>>,
+    locked: Res<LookLocked>,
 ) {
+    if locked.0 {
+        // drop the motion so the camera does not jump once it is unlocked
+        state.reader_motion.clear(&motion);
+        return;
+    }
     if let Ok(window) = primary_window.get_single() {
         for mut transform in query.iter_mut() {
             for ev in state.reader_motion.read(&motion) {
@@ -212,6 +222,7 @@ impl Plugin for PlayerPlugin {
         app.init_resource::<InputState>()
             .init_resource::<MovementSettings>()
             .init_resource::<KeyBindings>()
+            .init_resource::<LookLocked>()
             .add_systems(Startup, setup_player)
             .add_systems(Startup, initial_grab_cursor)
             // .add_systems(Update, player_move)
@@ -227,6 +238,7 @@ impl Plugin for CameraPluginV2 {
         app.init_resource::<InputState>()
             .init_resource::<MovementSettings>()
             .init_resource::<KeyBindings>()
+            .init_resource::<LookLocked>()
             .add_systems(Startup, initial_grab_cursor)
             .add_systems(Startup, initial_grab_on_flycam_spawn)
             // .add_systems(Update, player_move)
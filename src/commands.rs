@@ -0,0 +1,1694 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crate::console::CurrentDirectory;
+use crate::process::{Pid, ProcessTable};
+use crate::programs::{self, ProgramRegistry};
+use crate::render::{bold, paint, TermColor};
+use crate::shell::{self, ShellEnv};
+use crate::users::{PasswordPrompt, Session, Users, ROOT_USER};
+use crate::vfs::{Access, NodeId, NodeKind, Permissions, Vfs, VfsError, VfsPath, DEFAULT_USER};
+
+
+
+//                                                    COMMAND TYPES
+
+/// Exit status of a terminal command
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ExitStatus {
+    Success,
+    Failure(i32),
+}
+
+impl ExitStatus {
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Failure(code) => *code,
+        }
+    }
+}
+
+/// Side effects a command asks the terminal to perform once it has finished
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TerminalAction {
+    Clear,
+    Exit,
+    Edit(NodeId),              // open the file in the editor
+    Page(String, Vec<String>), // show a title and text in the pager
+}
+
+/// Everything a command produced: the lines it printed, its errors, its exit status and any terminal actions
+pub struct CommandOutput {
+    pub lines: Vec<String>,
+    pub errors: Vec<String>,
+    pub status: ExitStatus,
+    pub actions: Vec<TerminalAction>,
+}
+
+impl CommandOutput {
+    pub fn ok(lines: Vec<String>) -> Self {
+        CommandOutput { lines, errors: vec![], status: ExitStatus::Success, actions: vec![] }
+    }
+
+    pub fn empty() -> Self {
+        CommandOutput::ok(vec![])
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        let mut output = CommandOutput::empty();
+        output.push_error(message);
+        output
+    }
+
+    /// Records an error but lets the command carry on, for commands that work through several targets
+    pub fn push_error(&mut self, message: impl Into<String>) {
+        self.errors.push(paint(TermColor::Red, format!("! {}", message.into())));
+        self.status = ExitStatus::Failure(1);
+    }
+
+    pub fn with_action(mut self, action: TerminalAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+}
+
+/// A builtin or program that ran while a line was being executed, so the game can react to it afterwards
+#[derive(Clone, Debug)]
+pub struct ExecutedCommand {
+    pub name: String, // the command's name, or the program's file name however its path was typed
+    pub args: Vec<String>,
+    pub status: i32,
+}
+
+/// How many arguments a command accepts, and how to tell the player when they get it wrong
+#[derive(Clone, Copy, Debug)]
+pub struct ArgSpec {
+    pub min: usize,
+    pub max: Option<usize>,
+    pub usage: &'static str,
+}
+
+impl ArgSpec {
+    pub const fn none(usage: &'static str) -> Self {
+        ArgSpec { min: 0, max: Some(0), usage }
+    }
+
+    pub const fn exactly(count: usize, usage: &'static str) -> Self {
+        ArgSpec { min: count, max: Some(count), usage }
+    }
+
+    pub const fn range(min: usize, max: usize, usage: &'static str) -> Self {
+        ArgSpec { min, max: Some(max), usage }
+    }
+
+    pub const fn at_least(min: usize, usage: &'static str) -> Self {
+        ArgSpec { min, max: None, usage }
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.map_or(true, |max| count <= max)
+    }
+}
+
+/// What an argument refers to, used by tab completion
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ArgKind {
+    Path,
+    Directory,
+    Command,
+    Text,
+}
+
+/// The state a command is allowed to read and change while it runs
+pub struct CommandContext<'a> {
+    pub args: Vec<String>,
+    pub stdin: Vec<String>,
+    pub fs: &'a mut Vfs,
+    pub current_directory: &'a mut CurrentDirectory,
+    pub session: &'a mut Session,
+    pub users: &'a Users,
+    pub history: &'a [String],
+    pub env: &'a mut ShellEnv,
+    pub registry: &'a CommandRegistry,
+    pub programs: &'a ProgramRegistry,
+    pub processes: &'a mut ProcessTable,
+    pub executed: Vec<ExecutedCommand>, // every command that ran, including those in pipelines and scripts
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn cwd_path(&self) -> VfsPath {
+        self.fs.path_of(self.current_directory.0)
+    }
+
+    /// The user logged in on this console
+    pub fn user(&self) -> &str {
+        self.session.user()
+    }
+
+    /// Resolves a path argument against the current directory
+    pub fn resolve(&self, input: &str) -> Result<VfsPath, VfsError> {
+        VfsPath::parse(input, &self.cwd_path(), self.user())
+    }
+
+    /// Fails with permission denied unless the logged in user may access the node this way
+    pub fn check(&self, id: NodeId, access: Access) -> Result<(), VfsError> {
+        self.fs.check_access(id, self.user(), access)
+    }
+
+    /// Looks up a path the logged in user wants to access in this way
+    pub fn open(&self, path: &VfsPath, access: Access) -> Result<NodeId, VfsError> {
+        let id = self.fs.lookup(path)?;
+        self.check(id, access)?;
+        Ok(id)
+    }
+
+    /// Like [`CommandContext::check`] for a node and everything under it, checking directories for `dir_access`
+    /// and anything else for `access`
+    pub fn check_tree(&self, id: NodeId, access: Access, dir_access: Access) -> Result<(), VfsError> {
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            if self.fs.get(current).map_or(false, |node| node.is_dir()) {
+                self.check(current, dir_access)?;
+                stack.extend_from_slice(self.fs.children(current));
+            } else {
+                self.check(current, access)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The directory a new entry at `path` would go in, if the logged in user may add to it
+    pub fn writable_parent(&self, path: &VfsPath) -> Result<NodeId, VfsError> {
+        let parent = self.fs.lookup_dir(&path.parent().ok_or(VfsError::IsRoot)?)?;
+        self.check(parent, Access::WRITE)?;
+        Ok(parent)
+    }
+
+    /// Checks the logged in user could write to `path`, which means the file if it exists or its directory if not
+    pub fn check_write(&self, path: &VfsPath) -> Result<(), VfsError> {
+        match self.fs.lookup(path) {
+            Ok(id) => self.check(id, Access::WRITE),
+            Err(VfsError::NotFound(_)) => self.writable_parent(path).map(|_| ()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [`Vfs::write_path`] as the logged in user
+    pub fn write_path(&mut self, path: &VfsPath, text: &str, append: bool) -> Result<NodeId, VfsError> {
+        self.check_write(path)?;
+        self.fs.write_path(path, text, append, self.session.user())
+    }
+}
+
+impl From<VfsError> for CommandOutput {
+    fn from(err: VfsError) -> Self {
+        CommandOutput::error(err.to_string())
+    }
+}
+
+/// A single terminal command. Implement this and register it with
+/// [`RegisterTerminalCommand::register_terminal_command`] to make it available in every console
+pub trait TerminalCommand: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    /// One line saying what the command does, for `help`
+    fn summary(&self) -> &'static str;
+
+    /// The rest of the command's manual page, shown by `man` under its usage
+    fn description(&self) -> &'static str {
+        ""
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn args(&self) -> ArgSpec;
+
+    /// What the argument at `index` is, so tab knows what to complete it with
+    fn arg_kind(&self, _index: usize) -> ArgKind {
+        ArgKind::Path
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput;
+}
+
+
+
+//                                                    COMMAND REGISTRY
+
+#[derive(Resource, Default)]
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn TerminalCommand>>,
+    lookup: HashMap<String, usize>, // name or alias -> index into commands
+}
+
+impl CommandRegistry {
+    pub fn register<C: TerminalCommand>(&mut self, command: C) {
+        let index = self.commands.len();
+        self.lookup.insert(command.name().to_lowercase(), index);
+        for alias in command.aliases() {
+            self.lookup.insert(alias.to_lowercase(), index);
+        }
+        self.commands.push(Box::new(command));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn TerminalCommand> {
+        let index = self.lookup.get(&name.to_lowercase())?;
+        Some(self.commands[*index].as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn TerminalCommand> {
+        self.commands.iter().map(|command| command.as_ref())
+    }
+
+    /// Runs one command with its arguments after checking the argument count.
+    /// `stdin` is the output of the previous command in a pipeline
+    pub fn run(&self, argv: Vec<String>, stdin: Vec<String>, ctx: &mut CommandContext) -> CommandOutput {
+        let mut words = argv.into_iter();
+        let name = match words.next() {
+            Some(name) => name,
+            None => { return CommandOutput::empty(); }
+        };
+
+        // anything that is not a builtin is looked for as a program, so `./a.exe` and `a.exe` on $PATH both run
+        let command = match self.get(&name) {
+            Some(command) if !name.contains('/') => command,
+            _ => {
+                return match programs::find(&name, ctx) {
+                    Some(id) => programs::exec(id, std::iter::once(name).chain(words).collect(), stdin, ctx),
+                    None => CommandOutput::error(format!("unknown command '{}'", name)),
+                };
+            }
+        };
+
+        let args: Vec<String> = words.collect();
+        let spec = command.args();
+        if !spec.accepts(args.len()) {
+            return CommandOutput::error(format!("usage: {}", spec.usage));
+        }
+
+        // kept aside, commands like run execute others that replace ctx.args
+        ctx.args = args.clone();
+        ctx.stdin = stdin;
+        let output = command.execute(ctx);
+        ctx.executed.push(ExecutedCommand { name: String::from(command.name()), args, status: output.status.code() });
+        output
+    }
+}
+
+/// Splits `-r` style flags from the other arguments
+pub fn split_flags(args: &[String]) -> (Vec<char>, Vec<String>) {
+    let mut flags = vec![];
+    let mut operands = vec![];
+    for arg in args {
+        match arg.strip_prefix('-') {
+            Some(letters) if !letters.is_empty() => flags.extend(letters.chars()),
+            _ => operands.push(arg.clone()),
+        }
+    }
+    (flags, operands)
+}
+
+/// Matches a name against a pattern where `*` is any run of characters and `?` is any one
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None; // where the last * was, and how much of the name it has eaten
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last * take one more character and try again
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => { return false; }
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// `dir` and everything under it that the logged in user can see, parents before their children.
+/// Directories they may not look inside are reported and skipped
+fn descendants(ctx: &CommandContext, dir: NodeId, output: &mut CommandOutput) -> Vec<NodeId> {
+    let mut found = vec![];
+    let mut stack = vec![dir];
+    while let Some(id) = stack.pop() {
+        found.push(id);
+        if !ctx.fs.get(id).map_or(false, |node| node.is_dir()) {
+            continue;
+        }
+        match ctx.check(id, Access::READ | Access::EXECUTE) {
+            Ok(()) => stack.extend(ctx.fs.children(id).iter().rev()),
+            Err(err) => output.push_error(err.to_string()),
+        }
+    }
+    found
+}
+
+pub trait RegisterTerminalCommand {
+    fn register_terminal_command<C: TerminalCommand>(&mut self, command: C) -> &mut Self;
+}
+
+impl RegisterTerminalCommand for App {
+    fn register_terminal_command<C: TerminalCommand>(&mut self, command: C) -> &mut Self {
+        self.init_resource::<CommandRegistry>();
+        self.world.resource_mut::<CommandRegistry>().register(command);
+        self
+    }
+}
+
+/// Registers the built in terminal commands
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandRegistry>()
+            .register_terminal_command(Help)
+            .register_terminal_command(Man)
+            .register_terminal_command(Clear)
+            .register_terminal_command(Exit)
+            .register_terminal_command(Hello)
+            .register_terminal_command(Ls)
+            .register_terminal_command(Cd)
+            .register_terminal_command(History)
+            .register_terminal_command(Cat)
+            .register_terminal_command(Touch)
+            .register_terminal_command(Mkdir)
+            .register_terminal_command(Rm)
+            .register_terminal_command(Mv)
+            .register_terminal_command(Cp)
+            .register_terminal_command(Echo)
+            .register_terminal_command(Edit)
+            .register_terminal_command(Grep)
+            .register_terminal_command(Find)
+            .register_terminal_command(Tree)
+            .register_terminal_command(Head)
+            .register_terminal_command(Set)
+            .register_terminal_command(Export)
+            .register_terminal_command(Unset)
+            .register_terminal_command(Test)
+            .register_terminal_command(Run)
+            .register_terminal_command(Ps)
+            .register_terminal_command(Kill)
+            .register_terminal_command(Fg)
+            .register_terminal_command(Login)
+            .register_terminal_command(Logout)
+            .register_terminal_command(Whoami)
+            .register_terminal_command(Su)
+            .register_terminal_command(Chmod)
+            .register_terminal_command(StartServer);
+    }
+}
+
+
+
+//                                                    BUILT IN COMMANDS
+
+pub struct Help;
+
+impl TerminalCommand for Help {
+    fn name(&self) -> &'static str { "help" }
+    fn summary(&self) -> &'static str { "list the commands, or show how to use one" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 1, "help [command]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Command }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        if let Some(name) = ctx.args.first() {
+            return match ctx.registry.get(name) {
+                Some(command) => CommandOutput::ok(vec![format!("{} - {}", command.name(), command.summary()), format!("usage: {}", command.args().usage)]),
+                None => CommandOutput::error(format!("no help for '{}'", name)),
+            };
+        }
+
+        let mut lines = vec![bold("commands")];
+        let width = ctx.registry.iter().map(|command| command.name().len()).max().unwrap_or(0);
+        for command in ctx.registry.iter() {
+            lines.push(format!("  {:<width$} {}", command.name(), command.summary(), width = width));
+        }
+        lines.push(String::from("man <command> for the whole manual page"));
+        CommandOutput::ok(lines)
+    }
+}
+
+pub struct Man;
+
+impl Man {
+    /// A command's manual page, put together from its definition
+    fn page(command: &dyn TerminalCommand) -> Vec<String> {
+        let mut lines = vec![bold("NAME"), format!("    {} - {}", command.name(), command.summary())];
+        lines.extend([String::new(), bold("USAGE"), format!("    {}", command.args().usage)]);
+        if !command.aliases().is_empty() {
+            lines.extend([String::new(), bold("ALIASES"), format!("    {}", command.aliases().join(", "))]);
+        }
+        if !command.description().is_empty() {
+            lines.extend([String::new(), bold("DESCRIPTION")]);
+            lines.extend(command.description().lines().map(|line| format!("    {}", line)));
+        }
+        lines
+    }
+}
+
+impl TerminalCommand for Man {
+    fn name(&self) -> &'static str { "man" }
+    fn summary(&self) -> &'static str { "read the manual page of a command" }
+    fn args(&self) -> ArgSpec { ArgSpec::exactly(1, "man <command>") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Command }
+
+    fn description(&self) -> &'static str {
+        "Opens the page in the pager.\n\
+         \n\
+         space, b    next and previous page\n\
+         up, down    one line\n\
+         g, G        top and bottom\n\
+         q           quit"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let name = &ctx.args[0];
+        match ctx.registry.get(name) {
+            Some(command) => CommandOutput::empty().with_action(TerminalAction::Page(format!("man {}", command.name()), Man::page(command))),
+            None => CommandOutput::error(format!("no manual entry for '{}'", name)),
+        }
+    }
+}
+
+pub struct Clear;
+
+impl TerminalCommand for Clear {
+    fn name(&self) -> &'static str { "clear" }
+    fn summary(&self) -> &'static str { "clear the screen" }
+    fn aliases(&self) -> &'static [&'static str] { &["cls"] }
+    fn args(&self) -> ArgSpec { ArgSpec::none("clear") }
+
+    fn execute(&self, _ctx: &mut CommandContext) -> CommandOutput {
+        CommandOutput::empty().with_action(TerminalAction::Clear)
+    }
+}
+
+pub struct Exit;
+
+impl TerminalCommand for Exit {
+    fn name(&self) -> &'static str { "exit" }
+    fn summary(&self) -> &'static str { "step away from the terminal" }
+    fn aliases(&self) -> &'static [&'static str] { &["quit"] }
+    fn args(&self) -> ArgSpec { ArgSpec::none("exit") }
+
+    fn execute(&self, _ctx: &mut CommandContext) -> CommandOutput {
+        CommandOutput::empty().with_action(TerminalAction::Exit)
+    }
+}
+
+pub struct Hello;
+
+impl TerminalCommand for Hello {
+    fn name(&self) -> &'static str { "hello" }
+    fn summary(&self) -> &'static str { "say hello" }
+    fn args(&self) -> ArgSpec { ArgSpec::none("hello") }
+
+    fn execute(&self, _ctx: &mut CommandContext) -> CommandOutput {
+        CommandOutput::ok(vec![String::from("hello, my name is sumi :3")])
+    }
+}
+
+pub struct Ls;
+
+impl TerminalCommand for Ls {
+    fn name(&self) -> &'static str { "ls" }
+    fn summary(&self) -> &'static str { "list a directory" }
+    fn aliases(&self) -> &'static [&'static str] { &["dir"] }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 2, "ls [-l] [path]") }
+
+    fn description(&self) -> &'static str {
+        "Lists the entries of a directory, the current one if none is given.\n\
+         Directories show how many entries they hold, files show # and programs show !.\n\
+         \n\
+         -l  also show each entry's permissions and owner"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // -l adds each entry's permissions and owner
+        let (flags, operands) = split_flags(&ctx.args);
+        let long = flags.contains(&'l');
+        let path = match operands.first() {
+            Some(arg) => ctx.resolve(arg),
+            None => Ok(ctx.cwd_path()),
+        };
+        let (path, id) = match path.and_then(|path| ctx.open(&path, Access::READ).map(|id| (path, id))) {
+            Ok(found) => found,
+            Err(err) => { return err.into(); }
+        };
+
+        let mut lines = vec![path.to_string()];
+        let children = ctx.fs.children(id);
+        if !ctx.fs.get(id).map_or(false, |node| node.is_dir()) {
+            // listing a file just names it
+            return CommandOutput::ok(lines);
+        }
+
+        if children.is_empty() {
+            lines.push(String::from("~ empty"));
+        }
+        for child in children {
+            if let Some(node) = ctx.fs.get(*child) {
+                let (length, name) = match &node.kind {
+                    NodeKind::Directory { children } => (children.len().to_string(), paint(TermColor::Blue, &node.name)),
+                    NodeKind::Program => (String::from("!"), node.name.clone()),
+                    NodeKind::File { .. } => (String::from("#"), node.name.clone()),
+                };
+                if long {
+                    lines.push(format!("{} {:<6} [{}]{}", node.meta.permissions, node.meta.owner, length, name));
+                } else {
+                    lines.push(format!("[{}]{}", length, name));
+                }
+            }
+        }
+        CommandOutput::ok(lines)
+    }
+}
+
+pub struct Cd;
+
+impl TerminalCommand for Cd {
+    fn name(&self) -> &'static str { "cd" }
+    fn summary(&self) -> &'static str { "change the current directory" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 1, "cd [directory]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Directory }
+
+    fn description(&self) -> &'static str {
+        "With no directory, goes to your home directory.\n\
+         Paths can start with / for the root, ~ for your home or ~user for someone else's."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // plain cd goes home, like a real shell
+        let path = match ctx.args.first() {
+            Some(arg) => ctx.resolve(arg),
+            None => Ok(VfsPath::home(ctx.user())),
+        };
+
+        let found = path.and_then(|path| {
+            let id = ctx.fs.lookup_dir(&path)?;
+            ctx.check(id, Access::EXECUTE)?;
+            Ok((path, id))
+        });
+        match found {
+            Ok((path, id)) => {
+                ctx.current_directory.0 = id;
+                CommandOutput::ok(vec![path.to_string()])
+            }
+            Err(err) => err.into(),
+        }
+    }
+}
+
+pub struct History;
+
+impl TerminalCommand for History {
+    fn name(&self) -> &'static str { "history" }
+    fn summary(&self) -> &'static str { "list the commands entered on this terminal" }
+    fn args(&self) -> ArgSpec { ArgSpec::none("history") }
+
+    fn description(&self) -> &'static str {
+        "Up and down on the prompt step through the history, ctrl+r searches it."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let lines = ctx.history.iter()
+            .enumerate()
+            .map(|(i, entry)| format!("{:>4}  {}", i + 1, entry))
+            .collect();
+        CommandOutput::ok(lines)
+    }
+}
+
+pub struct Cat;
+
+impl TerminalCommand for Cat {
+    fn name(&self) -> &'static str { "cat" }
+    fn summary(&self) -> &'static str { "print files" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(0, "cat [file]...") }
+
+    fn description(&self) -> &'static str {
+        "Prints each file in turn. With no files, passes its input through."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // with no files cat passes its input through
+        if ctx.args.is_empty() {
+            return CommandOutput::ok(std::mem::take(&mut ctx.stdin));
+        }
+
+        let mut output = CommandOutput::empty();
+        for arg in &ctx.args {
+            let content = ctx.resolve(arg)
+                .and_then(|path| ctx.open(&path, Access::READ))
+                .and_then(|id| ctx.fs.read_file(id));
+            match content {
+                Ok(content) => output.lines.extend(content.lines().map(String::from)),
+                Err(err) => output.push_error(err.to_string()),
+            }
+        }
+        output
+    }
+}
+
+pub struct Touch;
+
+impl TerminalCommand for Touch {
+    fn name(&self) -> &'static str { "touch" }
+    fn summary(&self) -> &'static str { "create empty files or update their time" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "touch <file>...") }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut output = CommandOutput::empty();
+        for arg in ctx.args.clone() {
+            let touched = ctx.resolve(&arg).and_then(|path| match ctx.fs.lookup(&path) {
+                Ok(id) => {
+                    ctx.check(id, Access::WRITE)?;
+                    ctx.fs.touch(id);
+                    Ok(id)
+                }
+                Err(VfsError::NotFound(_)) => ctx.write_path(&path, "", true),
+                Err(err) => Err(err),
+            });
+            if let Err(err) = touched {
+                output.push_error(err.to_string());
+            }
+        }
+        output
+    }
+}
+
+pub struct Mkdir;
+
+impl TerminalCommand for Mkdir {
+    fn name(&self) -> &'static str { "mkdir" }
+    fn summary(&self) -> &'static str { "create directories" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "mkdir <directory>...") }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut output = CommandOutput::empty();
+        for arg in ctx.args.clone() {
+            let created = ctx.resolve(&arg).and_then(|path| {
+                let parent = ctx.writable_parent(&path)?;
+                let owner = String::from(ctx.user());
+                ctx.fs.create(parent, path.file_name().unwrap_or(""), NodeKind::Directory { children: vec![] }, &owner)
+            });
+            if let Err(err) = created {
+                output.push_error(err.to_string());
+            }
+        }
+        output
+    }
+}
+
+pub struct Rm;
+
+impl TerminalCommand for Rm {
+    fn name(&self) -> &'static str { "rm" }
+    fn summary(&self) -> &'static str { "remove files and directories" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "rm [-r] <path>...") }
+
+    fn description(&self) -> &'static str {
+        "-r  remove directories and everything in them"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let (flags, targets) = split_flags(&ctx.args);
+        let recursive = flags.contains(&'r') || flags.contains(&'R');
+        if targets.is_empty() {
+            return CommandOutput::error(format!("usage: {}", self.args().usage));
+        }
+
+        let mut output = CommandOutput::empty();
+        for arg in targets {
+            let removed = ctx.resolve(&arg).and_then(|path| {
+                let id = ctx.fs.lookup(&path)?;
+                if !recursive && ctx.fs.get(id).map_or(false, |node| node.is_dir()) {
+                    return Err(VfsError::IsADirectory(path));
+                }
+                ctx.writable_parent(&path)?;
+                // emptying a directory is writing to it, so every directory in the tree has to allow that
+                ctx.check_tree(id, Access::NONE, Access::WRITE | Access::EXECUTE)?;
+                ctx.fs.remove(id)
+            });
+            if let Err(err) = removed {
+                output.push_error(err.to_string());
+            }
+        }
+
+        // the directory we were standing in might be gone now
+        if !ctx.fs.exists(ctx.current_directory.0) {
+            ctx.current_directory.0 = Vfs::ROOT;
+        }
+        output
+    }
+}
+
+/// Where a `mv` or `cp` should put `source`: into `dest` if it is a directory, otherwise at `dest` itself.
+/// Either way the logged in user has to be able to write there
+fn destination(ctx: &CommandContext, source: NodeId, dest: &str) -> Result<(NodeId, String), VfsError> {
+    let path = ctx.resolve(dest)?;
+    match ctx.fs.lookup(&path) {
+        Ok(id) if ctx.fs.get(id).map_or(false, |node| node.is_dir()) => {
+            ctx.check(id, Access::WRITE)?;
+            let name = ctx.fs.get(source).map(|node| node.name.clone()).unwrap_or_default();
+            Ok((id, name))
+        }
+        Ok(_) => Err(VfsError::AlreadyExists(path)),
+        Err(VfsError::NotFound(_)) => {
+            let parent = ctx.writable_parent(&path)?;
+            Ok((parent, String::from(path.file_name().unwrap_or(""))))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub struct Mv;
+
+impl TerminalCommand for Mv {
+    fn name(&self) -> &'static str { "mv" }
+    fn summary(&self) -> &'static str { "move or rename a file or directory" }
+    fn args(&self) -> ArgSpec { ArgSpec::exactly(2, "mv <source> <destination>") }
+
+    fn description(&self) -> &'static str {
+        "If the destination is a directory the source is moved into it, otherwise it is renamed to the destination."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let moved = ctx.resolve(&ctx.args[0])
+            .and_then(|path| {
+                let source = ctx.fs.lookup(&path)?;
+                ctx.writable_parent(&path)?;
+                Ok(source)
+            })
+            .and_then(|source| {
+                let (parent, name) = destination(ctx, source, &ctx.args[1])?;
+                ctx.fs.move_node(source, parent, &name)
+            });
+        match moved {
+            Ok(()) => CommandOutput::empty(),
+            Err(err) => err.into(),
+        }
+    }
+}
+
+pub struct Cp;
+
+impl TerminalCommand for Cp {
+    fn name(&self) -> &'static str { "cp" }
+    fn summary(&self) -> &'static str { "copy a file or directory" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(2, 3, "cp [-r] <source> <destination>") }
+
+    fn description(&self) -> &'static str {
+        "If the destination is a directory the copy goes into it.\n\
+         \n\
+         -r  copy directories and everything in them"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let (flags, operands) = split_flags(&ctx.args);
+        let recursive = flags.contains(&'r') || flags.contains(&'R');
+        if operands.len() != 2 {
+            return CommandOutput::error(format!("usage: {}", self.args().usage));
+        }
+
+        let copied = ctx.resolve(&operands[0]).and_then(|path| {
+            let source = ctx.open(&path, Access::READ)?;
+            if !recursive && ctx.fs.get(source).map_or(false, |node| node.is_dir()) {
+                return Err(VfsError::IsADirectory(path));
+            }
+            // the copies belong to whoever makes them, so they may only copy what they could read
+            ctx.check_tree(source, Access::READ, Access::READ | Access::EXECUTE)?;
+            let (parent, name) = destination(ctx, source, &operands[1])?;
+            let owner = String::from(ctx.user());
+            ctx.fs.copy_node(source, parent, &name, &owner)
+        });
+        match copied {
+            Ok(_) => CommandOutput::empty(),
+            Err(err) => err.into(),
+        }
+    }
+}
+
+pub struct Echo;
+
+impl TerminalCommand for Echo {
+    fn name(&self) -> &'static str { "echo" }
+    fn summary(&self) -> &'static str { "print text" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(0, "echo [text]...") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn description(&self) -> &'static str {
+        "Joins its arguments with spaces. Use > file or >> file to write the text into a file."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        CommandOutput::ok(vec![ctx.args.join(" ")])
+    }
+}
+
+pub struct Edit;
+
+impl TerminalCommand for Edit {
+    fn name(&self) -> &'static str { "edit" }
+    fn summary(&self) -> &'static str { "edit a file" }
+    fn aliases(&self) -> &'static [&'static str] { &["nano"] }
+    fn args(&self) -> ArgSpec { ArgSpec::exactly(1, "edit <file>") }
+
+    fn description(&self) -> &'static str {
+        "Opens the file in the editor, creating it if it does not exist.\n\
+         You need permission to both read and write it.\n\
+         \n\
+         ^S  save\n\
+         ^Q  quit, twice if there are unsaved changes"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // editing a file that does not exist yet creates it
+        // the editor saves straight to the file, so it has to be writable as well
+        let file = ctx.resolve(&ctx.args[0]).and_then(|path| match ctx.fs.lookup(&path) {
+            Err(VfsError::NotFound(_)) => ctx.write_path(&path, "", true),
+            Ok(id) => ctx.check(id, Access::READ | Access::WRITE).map(|_| id),
+            Err(err) => Err(err),
+        });
+        match file.and_then(|file| ctx.fs.read_file(file).map(|_| file)) {
+            Ok(file) => CommandOutput::empty().with_action(TerminalAction::Edit(file)),
+            Err(err) => err.into(),
+        }
+    }
+}
+
+pub struct Grep;
+
+impl TerminalCommand for Grep {
+    fn name(&self) -> &'static str { "grep" }
+    fn summary(&self) -> &'static str { "search text for a pattern" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "grep [-i] [-n] [-r] <pattern> [file]...") }
+    fn arg_kind(&self, index: usize) -> ArgKind { if index == 0 { ArgKind::Text } else { ArgKind::Path } }
+
+    fn description(&self) -> &'static str {
+        "Prints the lines that contain the pattern. With files, every match is shown with its file and line number, otherwise grep filters its input.\n\
+         \n\
+         -i  ignore case\n\
+         -n  number the lines of its input\n\
+         -r  search every file under the directories given"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let (flags, operands) = split_flags(&ctx.args);
+        let (ignore_case, numbered, recursive) = (flags.contains(&'i'), flags.contains(&'n'), flags.contains(&'r'));
+        let (pattern, files) = match operands.split_first() {
+            Some((pattern, files)) => (pattern.clone(), files.to_vec()),
+            None => { return CommandOutput::error(format!("usage: {}", self.args().usage)); }
+        };
+        let pattern = if ignore_case { pattern.to_lowercase() } else { pattern };
+        let matches = |line: &str| {
+            if ignore_case { line.to_lowercase().contains(&pattern) } else { line.contains(&pattern) }
+        };
+
+        // with no files grep filters its input
+        let mut output = CommandOutput::empty();
+        if files.is_empty() {
+            for (i, line) in ctx.stdin.iter().enumerate().filter(|(_, line)| matches(line)) {
+                output.lines.push(if numbered { format!("{}: {}", paint(TermColor::Yellow, i + 1), line) } else { line.clone() });
+            }
+        }
+
+        // matches in files are always shown with where they are
+        for arg in files {
+            let targets = match ctx.resolve(&arg).and_then(|path| ctx.fs.lookup(&path)) {
+                Ok(id) if recursive => descendants(ctx, id, &mut output),
+                Ok(id) => vec![id],
+                Err(err) => { output.push_error(err.to_string()); continue; }
+            };
+            for id in targets {
+                let path = ctx.fs.path_of(id);
+                let content = match ctx.fs.read_file(id) {
+                    Ok(content) => content,
+                    // programs and directories found by -r are passed over quietly
+                    Err(_) if recursive => continue,
+                    Err(err) => { output.push_error(err.to_string()); continue; }
+                };
+                if let Err(err) = ctx.check(id, Access::READ) {
+                    output.push_error(err.to_string());
+                    continue;
+                }
+                for (i, line) in content.lines().enumerate().filter(|(_, line)| matches(line)) {
+                    output.lines.push(format!("{}:{}: {}", paint(TermColor::Magenta, &path), paint(TermColor::Yellow, i + 1), line));
+                }
+            }
+        }
+
+        // like grep, finding nothing is a failure but not an error
+        if output.lines.is_empty() && output.status == ExitStatus::Success {
+            output.status = ExitStatus::Failure(1);
+        }
+        output
+    }
+}
+
+pub struct Find;
+
+impl TerminalCommand for Find {
+    fn name(&self) -> &'static str { "find" }
+    fn summary(&self) -> &'static str { "find files and directories by name" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(0, "find [dir] [-name <pattern>] [-type f|d]") }
+
+    fn description(&self) -> &'static str {
+        "Lists everything under a directory, the current one if none is given.\n\
+         \n\
+         -name <pattern>  only names matching the pattern, where * is anything and ? is any one character\n\
+         -type f|d        only files or only directories"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut start = None;
+        let mut name = None;
+        let mut kind = None;
+        let mut args = ctx.args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-name" | "-type" => {
+                    let value = match args.next() {
+                        Some(value) => value.clone(),
+                        None => { return CommandOutput::error(format!("{} needs a value. usage: {}", arg, self.args().usage)); }
+                    };
+                    if arg == "-name" { name = Some(value); } else { kind = Some(value); }
+                }
+                _ if start.is_none() && !arg.starts_with('-') => start = Some(arg.clone()),
+                _ => { return CommandOutput::error(format!("usage: {}", self.args().usage)); }
+            }
+        }
+        let want_dir = match kind.as_deref() {
+            None => None,
+            Some("d") => Some(true),
+            Some("f") => Some(false),
+            Some(other) => { return CommandOutput::error(format!("-type {}: use f for files or d for directories", other)); }
+        };
+
+        let start = match ctx.resolve(start.as_deref().unwrap_or(".")).and_then(|path| ctx.open(&path, Access::NONE)) {
+            Ok(id) => id,
+            Err(err) => { return err.into(); }
+        };
+        let mut output = CommandOutput::empty();
+        for id in descendants(ctx, start, &mut output) {
+            let node = match ctx.fs.get(id) {
+                Some(node) => node,
+                None => continue,
+            };
+            let name_matches = name.as_ref().map_or(true, |pattern| glob_match(pattern, &node.name));
+            let kind_matches = want_dir.map_or(true, |dir| node.is_dir() == dir);
+            if name_matches && kind_matches {
+                output.lines.push(ctx.fs.path_of(id).to_string());
+            }
+        }
+        output
+    }
+}
+
+pub struct Tree;
+
+impl Tree {
+    /// Draws the children of `dir` below it, `prefix` carrying the lines of the levels above
+    fn draw(ctx: &CommandContext, dir: NodeId, prefix: &str, depth: usize, max_depth: Option<usize>, output: &mut CommandOutput, counts: &mut (usize, usize)) {
+        let children = ctx.fs.children(dir);
+        for (i, child) in children.iter().enumerate() {
+            let node = match ctx.fs.get(*child) {
+                Some(node) => node,
+                None => continue,
+            };
+            let last = i + 1 == children.len();
+            let branch = if last { "`-- " } else { "|-- " };
+            if !node.is_dir() {
+                counts.1 += 1;
+                output.lines.push(format!("{}{}{}", prefix, branch, node.name));
+                continue;
+            }
+
+            counts.0 += 1;
+            let locked = ctx.check(*child, Access::READ | Access::EXECUTE).is_err();
+            let note = if locked { " [permission denied]" } else { "" };
+            output.lines.push(format!("{}{}{}{}", prefix, branch, paint(TermColor::Blue, &node.name), note));
+            if !locked && max_depth.map_or(true, |max| depth < max) {
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "|   " });
+                Tree::draw(ctx, *child, &prefix, depth + 1, max_depth, output, counts);
+            }
+        }
+    }
+}
+
+impl TerminalCommand for Tree {
+    fn name(&self) -> &'static str { "tree" }
+    fn summary(&self) -> &'static str { "draw a directory and everything in it" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 3, "tree [-L depth] [dir]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Directory }
+
+    fn description(&self) -> &'static str {
+        "-L <depth>  go no deeper than this many levels"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut dir = None;
+        let mut max_depth = None;
+        let mut args = ctx.args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-L" => match args.next().and_then(|depth| depth.parse::<usize>().ok()).filter(|depth| *depth > 0) {
+                    Some(depth) => max_depth = Some(depth),
+                    None => { return CommandOutput::error("-L needs a depth of at least 1"); }
+                },
+                _ if dir.is_none() => dir = Some(arg.clone()),
+                _ => { return CommandOutput::error(format!("usage: {}", self.args().usage)); }
+            }
+        }
+
+        let found = ctx.resolve(dir.as_deref().unwrap_or(".")).and_then(|path| {
+            let id = ctx.fs.lookup_dir(&path)?;
+            ctx.check(id, Access::READ | Access::EXECUTE)?;
+            Ok((path, id))
+        });
+        let (path, id) = match found {
+            Ok(found) => found,
+            Err(err) => { return err.into(); }
+        };
+
+        let mut output = CommandOutput::ok(vec![paint(TermColor::Blue, &path)]);
+        let mut counts = (0, 0); // directories, files
+        Tree::draw(ctx, id, "", 1, max_depth, &mut output, &mut counts);
+        output.lines.push(format!("{} directories, {} files", counts.0, counts.1));
+        output
+    }
+}
+
+pub struct Head;
+
+impl TerminalCommand for Head {
+    fn name(&self) -> &'static str { "head" }
+    fn summary(&self) -> &'static str { "print the first lines of a file or of its input" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 2, "head [count] [file]") }
+
+    fn description(&self) -> &'static str {
+        "Prints 10 lines unless given a count, as in head 5 notes.txt or head -5."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut count = 10;
+        let mut file = None;
+        for arg in &ctx.args {
+            match arg.trim_start_matches('-').parse::<usize>() {
+                Ok(n) if file.is_none() => count = n,
+                _ => file = Some(arg.clone()),
+            }
+        }
+
+        let lines = match file {
+            Some(file) => {
+                let content = ctx.resolve(&file)
+                    .and_then(|path| ctx.open(&path, Access::READ))
+                    .and_then(|id| ctx.fs.read_file(id));
+                match content {
+                    Ok(content) => content.lines().map(String::from).collect(),
+                    Err(err) => { return err.into(); }
+                }
+            }
+            None => std::mem::take(&mut ctx.stdin),
+        };
+        CommandOutput::ok(lines.into_iter().take(count).collect())
+    }
+}
+
+/// Reads `NAME=value` or `NAME value...` from set and export arguments
+fn assignment(args: &[String]) -> Result<(String, Option<String>), String> {
+    let (name, value) = match args[0].split_once('=') {
+        Some((name, value)) => (name, Some(String::from(value))),
+        None if args.len() > 1 => (args[0].as_str(), Some(args[1..].join(" "))),
+        None => (args[0].as_str(), None),
+    };
+    if !shell::is_valid_name(name) {
+        return Err(format!("'{}' is not a valid variable name", name));
+    }
+    Ok((String::from(name), value))
+}
+
+pub struct Set;
+
+impl TerminalCommand for Set {
+    fn name(&self) -> &'static str { "set" }
+    fn summary(&self) -> &'static str { "set a shell variable, or list them all" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(0, "set [name[=value] | name value...]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn description(&self) -> &'static str {
+        "Variables are used as $NAME. Exported variables are marked with export."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        if ctx.args.is_empty() {
+            let lines = ctx.env.vars().into_iter()
+                .map(|(name, value, exported)| format!("{}{}={}", if exported { "export " } else { "" }, name, value))
+                .collect();
+            return CommandOutput::ok(lines);
+        }
+
+        match assignment(&ctx.args) {
+            Ok((name, value)) => {
+                ctx.env.set(&name, &value.unwrap_or_default());
+                CommandOutput::empty()
+            }
+            Err(message) => CommandOutput::error(message),
+        }
+    }
+}
+
+pub struct Export;
+
+impl TerminalCommand for Export {
+    fn name(&self) -> &'static str { "export" }
+    fn summary(&self) -> &'static str { "set a variable and mark it exported" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "export name[=value] | name value...") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        match assignment(&ctx.args) {
+            Ok((name, value)) => {
+                // `export NAME` exports what is already set
+                let value = value.or_else(|| ctx.env.get(&name)).unwrap_or_default();
+                ctx.env.export(&name, &value);
+                CommandOutput::empty()
+            }
+            Err(message) => CommandOutput::error(message),
+        }
+    }
+}
+
+pub struct Unset;
+
+impl TerminalCommand for Unset {
+    fn name(&self) -> &'static str { "unset" }
+    fn summary(&self) -> &'static str { "remove shell variables" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "unset <name>...") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        for name in &ctx.args {
+            ctx.env.unset(name);
+        }
+        CommandOutput::empty()
+    }
+}
+
+pub struct Test;
+
+impl Test {
+    fn evaluate(args: &[String], ctx: &CommandContext) -> Result<bool, String> {
+        let exists = |path: &str, dir: Option<bool>| {
+            ctx.resolve(path)
+                .and_then(|path| ctx.fs.lookup(&path))
+                .ok()
+                .and_then(|id| ctx.fs.get(id))
+                .map_or(false, |node| dir.map_or(true, |dir| node.is_dir() == dir))
+        };
+        let number = |arg: &String| arg.parse::<i64>().map_err(|_| format!("'{}' is not a number", arg));
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            [] => Ok(false),
+            ["!", rest @ ..] => {
+                let rest: Vec<String> = rest.iter().map(|arg| String::from(*arg)).collect();
+                Test::evaluate(&rest, ctx).map(|result| !result)
+            }
+            [value] => Ok(!value.is_empty()),
+            ["-z", value] => Ok(value.is_empty()),
+            ["-n", value] => Ok(!value.is_empty()),
+            ["-e", path] => Ok(exists(path, None)),
+            ["-f", path] => Ok(exists(path, Some(false))),
+            ["-d", path] => Ok(exists(path, Some(true))),
+            [a, "=", b] | [a, "==", b] => Ok(a == b),
+            [a, "!=", b] => Ok(a != b),
+            [a, op, b] => {
+                let (a, b) = (number(&String::from(*a))?, number(&String::from(*b))?);
+                match *op {
+                    "-eq" => Ok(a == b),
+                    "-ne" => Ok(a != b),
+                    "-lt" => Ok(a < b),
+                    "-le" => Ok(a <= b),
+                    "-gt" => Ok(a > b),
+                    "-ge" => Ok(a >= b),
+                    _ => Err(format!("unknown operator '{}'", op)),
+                }
+            }
+            _ => Err(String::from("too many arguments")),
+        }
+    }
+}
+
+impl TerminalCommand for Test {
+    fn name(&self) -> &'static str { "test" }
+    fn summary(&self) -> &'static str { "check a condition, for if and while" }
+    fn aliases(&self) -> &'static [&'static str] { &["["] }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(0, "test <expression>") }
+
+    fn description(&self) -> &'static str {
+        "Succeeds when the expression is true. Can also be written [ expression ].\n\
+         \n\
+         -e path, -f path, -d path   exists, is a file, is a directory\n\
+         -z text, -n text            is empty, is not empty\n\
+         a = b, a != b               text comparison\n\
+         a -eq b, -ne, -lt, -le, -gt, -ge   number comparison\n\
+         ! expression                negation"
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // `[ a = b ]` is the same as `test a = b`
+        let mut args = ctx.args.clone();
+        if args.last().map(String::as_str) == Some("]") {
+            args.pop();
+        }
+
+        match Test::evaluate(&args, ctx) {
+            Ok(true) => CommandOutput::empty(),
+            Ok(false) => {
+                let mut output = CommandOutput::empty();
+                output.status = ExitStatus::Failure(1);
+                output
+            }
+            Err(message) => {
+                let mut output = CommandOutput::error(message);
+                output.status = ExitStatus::Failure(2);
+                output
+            }
+        }
+    }
+}
+
+pub struct Run;
+
+impl TerminalCommand for Run {
+    fn name(&self) -> &'static str { "run" }
+    fn summary(&self) -> &'static str { "run a script or a program" }
+    fn aliases(&self) -> &'static [&'static str] { &["sh"] }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "run <script|program> [args]...") }
+
+    fn description(&self) -> &'static str {
+        "Scripts are files of commands. Their arguments are $1, $2 and so on."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // programs are found on $PATH like any command, scripts relative to the current directory
+        let found = match programs::find(&ctx.args[0], ctx) {
+            Some(id) => Ok(id),
+            None => ctx.resolve(&ctx.args[0]).and_then(|path| ctx.fs.lookup(&path)),
+        };
+        let id = match found {
+            Ok(id) => id,
+            Err(err) => { return err.into(); }
+        };
+        if let Some(NodeKind::Program) = ctx.fs.get(id).map(|node| &node.kind) {
+            let argv = ctx.args.clone();
+            let stdin = std::mem::take(&mut ctx.stdin);
+            return programs::exec(id, argv, stdin, ctx);
+        }
+
+        let script = ctx.check(id, Access::READ).and_then(|_| ctx.fs.read_file(id).map(String::from));
+        match script {
+            Ok(script) => {
+                let args = ctx.args.clone();
+                shell::run_script(&script, args, ctx)
+            }
+            Err(err) => err.into(),
+        }
+    }
+}
+
+pub struct Ps;
+
+impl TerminalCommand for Ps {
+    fn name(&self) -> &'static str { "ps" }
+    fn summary(&self) -> &'static str { "list running processes" }
+    fn args(&self) -> ArgSpec { ArgSpec::none("ps") }
+
+    fn description(&self) -> &'static str {
+        "End a command with & to start it in the background."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut lines = vec![bold("  PID STAT   TIME COMMAND")];
+        for entry in ctx.processes.iter() {
+            let stat = if entry.background { "bg" } else { "fg" };
+            lines.push(format!("{:>5} {:<4} {:>5}s {}", entry.pid, stat, entry.elapsed as u32, entry.command));
+        }
+        CommandOutput::ok(lines)
+    }
+}
+
+fn parse_pid(arg: &str) -> Result<Pid, String> {
+    arg.trim_start_matches('%').parse::<Pid>().map_err(|_| format!("{}: not a pid", arg))
+}
+
+pub struct Kill;
+
+impl TerminalCommand for Kill {
+    fn name(&self) -> &'static str { "kill" }
+    fn summary(&self) -> &'static str { "stop processes" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(1, "kill <pid>...") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn description(&self) -> &'static str {
+        "Takes pids as shown by ps, with or without a leading %."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let mut output = CommandOutput::empty();
+        for arg in ctx.args.clone() {
+            let pid = match parse_pid(&arg) {
+                Ok(pid) => pid,
+                Err(err) => { output.push_error(err); continue; }
+            };
+            match ctx.processes.kill(pid) {
+                Some(entry) => output.lines.push(format!("[{}] killed  {}", entry.pid, entry.command)),
+                None => output.push_error(format!("{}: no such process", pid)),
+            }
+        }
+        output
+    }
+}
+
+pub struct Fg;
+
+impl TerminalCommand for Fg {
+    fn name(&self) -> &'static str { "fg" }
+    fn summary(&self) -> &'static str { "bring a background process to the foreground" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 1, "fg [pid]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn description(&self) -> &'static str {
+        "With no pid, picks the most recently started one. ctrl+c stops the foreground process."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let pid = match ctx.args.first() {
+            Some(arg) => match parse_pid(arg) {
+                Ok(pid) => pid,
+                Err(err) => { return CommandOutput::error(err); }
+            },
+            None => match ctx.processes.last_background() {
+                Some(pid) => pid,
+                None => { return CommandOutput::error("no background processes"); }
+            },
+        };
+        if !ctx.processes.set_foreground(pid) {
+            return CommandOutput::error(format!("{}: no such process", pid));
+        }
+        let command = ctx.processes.get(pid).map(|entry| entry.command.clone()).unwrap_or_default();
+        CommandOutput::ok(vec![command])
+    }
+}
+
+/// Logs in straight away when the password was given or is not needed, otherwise leaves the console asking for it
+fn start_login(ctx: &mut CommandContext, user: &str, password: Option<&String>, switch: bool) -> CommandOutput {
+    let account = match ctx.users.get(user) {
+        Some(account) => account,
+        None => { return CommandOutput::error(format!("{}: no such user", user)); }
+    };
+
+    // root can become anyone without knowing their password
+    let needs_password = account.needs_password() && !(switch && ctx.session.is_root());
+    match password {
+        Some(password) => {
+            let users = ctx.users;
+            match ctx.session.authenticate(users, user, password, switch) {
+                Ok(()) => CommandOutput::ok(vec![format!("logged in as {}", user)]),
+                Err(err) => CommandOutput::error(err),
+            }
+        }
+        None if needs_password => {
+            ctx.session.prompt = Some(PasswordPrompt { user: String::from(user), switch, input: String::new() });
+            CommandOutput::empty()
+        }
+        None => {
+            if switch { ctx.session.switch(user); } else { ctx.session.login(user); }
+            CommandOutput::ok(vec![format!("logged in as {}", user)])
+        }
+    }
+}
+
+pub struct Login;
+
+impl TerminalCommand for Login {
+    fn name(&self) -> &'static str { "login" }
+    fn summary(&self) -> &'static str { "log in as another user" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(1, 2, "login <user> [password]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn description(&self) -> &'static str {
+        "Asks for the password if it is not given. Replaces the whole session, unlike su."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let (user, password) = (ctx.args[0].clone(), ctx.args.get(1).cloned());
+        start_login(ctx, &user, password.as_ref(), false)
+    }
+}
+
+pub struct Su;
+
+impl TerminalCommand for Su {
+    fn name(&self) -> &'static str { "su" }
+    fn summary(&self) -> &'static str { "become another user until logout" }
+    fn args(&self) -> ArgSpec { ArgSpec::range(0, 2, "su [user] [password]") }
+    fn arg_kind(&self, _index: usize) -> ArgKind { ArgKind::Text }
+
+    fn description(&self) -> &'static str {
+        "Becomes root if no user is given. Asks for the password if it is not given, unless you are already root."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let user = ctx.args.first().cloned().unwrap_or_else(|| String::from(ROOT_USER));
+        let password = ctx.args.get(1).cloned();
+        start_login(ctx, &user, password.as_ref(), true)
+    }
+}
+
+pub struct Logout;
+
+impl TerminalCommand for Logout {
+    fn name(&self) -> &'static str { "logout" }
+    fn summary(&self) -> &'static str { "go back to the user before the last su" }
+    fn args(&self) -> ArgSpec { ArgSpec::none("logout") }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        // undo the last su, or fall back to the console's own account
+        if !ctx.session.logout() {
+            if ctx.user() == DEFAULT_USER {
+                return CommandOutput::error(format!("{} is the console's own account", DEFAULT_USER));
+            }
+            ctx.session.login(DEFAULT_USER);
+        }
+        CommandOutput::ok(vec![format!("logged in as {}", ctx.user())])
+    }
+}
+
+pub struct Whoami;
+
+impl TerminalCommand for Whoami {
+    fn name(&self) -> &'static str { "whoami" }
+    fn summary(&self) -> &'static str { "print the user you are logged in as" }
+    fn args(&self) -> ArgSpec { ArgSpec::none("whoami") }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        CommandOutput::ok(vec![String::from(ctx.user())])
+    }
+}
+
+pub struct Chmod;
+
+impl TerminalCommand for Chmod {
+    fn name(&self) -> &'static str { "chmod" }
+    fn summary(&self) -> &'static str { "change who may read, write and run a node" }
+    fn args(&self) -> ArgSpec { ArgSpec::at_least(2, "chmod <rwxrwx> <path>...") }
+    fn arg_kind(&self, index: usize) -> ArgKind { if index == 0 { ArgKind::Text } else { ArgKind::Path } }
+
+    fn description(&self) -> &'static str {
+        "Permissions are written as six letters, what the owner may do and then what everyone else may do, as in rw-r--.\n\
+         r is read, w is write and x is run a program or go into a directory. Only the owner and root can change them."
+    }
+
+    fn execute(&self, ctx: &mut CommandContext) -> CommandOutput {
+        let permissions = match Permissions::parse(&ctx.args[0]) {
+            Some(permissions) => permissions,
+            None => { return CommandOutput::error(format!("'{}' is not a permission string like rw-r--", ctx.args[0])); }
+        };
+
+        let mut output = CommandOutput::empty();
+        for arg in ctx.args[1..].to_vec() {
+            // only the owner, or root, may change a node's permissions
+            let changed = ctx.resolve(&arg).and_then(|path| {
+                let id = ctx.open(&path, Access::NONE)?;
+                let owner = ctx.fs.get(id).map(|node| node.meta.owner.clone()).unwrap_or_default();
+                if owner != ctx.user() && !ctx.session.is_root() {
+                    return Err(VfsError::PermissionDenied(path));
+                }
+                ctx.fs.set_permissions(id, permissions);
+                Ok(())
+            });
+            if let Err(err) = changed {
+                output.push_error(err.to_string());
+            }
+        }
+        output
+    }
+}
+
+pub struct StartServer;
+
+impl TerminalCommand for StartServer {
+    fn name(&self) -> &'static str { "start-server" }
+    fn summary(&self) -> &'static str { "start a multiplayer server" }
+    fn args(&self) -> ArgSpec { ArgSpec::none("start-server") }
+
+    fn execute(&self, _ctx: &mut CommandContext) -> CommandOutput {
+        // networking is not hooked up yet
+        CommandOutput::empty()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::ProgramsPlugin;
+    use crate::{render, vfs};
+
+    /// What a console keeps between lines, on the built in filesystem, logged in as the default user
+    struct TestConsole {
+        fs: Vfs,
+        current_directory: CurrentDirectory,
+        session: Session,
+        users: Users,
+        env: ShellEnv,
+        processes: ProcessTable,
+        registry: CommandRegistry,
+        programs: ProgramRegistry,
+    }
+
+    impl TestConsole {
+        fn new() -> Self {
+            let mut app = App::new();
+            app.add_plugins((CommandsPlugin, ProgramsPlugin));
+            TestConsole {
+                fs: Vfs::from_directory(&vfs::built_in_definition()),
+                current_directory: CurrentDirectory::default(),
+                session: Session::default(),
+                users: Users::default(),
+                env: ShellEnv::default(),
+                processes: ProcessTable::default(),
+                registry: app.world.remove_resource::<CommandRegistry>().unwrap(),
+                programs: app.world.remove_resource::<ProgramRegistry>().unwrap(),
+            }
+        }
+
+        /// Runs a line and returns its errors as plain text
+        fn run(&mut self, line: &str) -> Vec<String> {
+            let mut ctx = CommandContext {
+                args: vec![],
+                stdin: vec![],
+                fs: &mut self.fs,
+                current_directory: &mut self.current_directory,
+                session: &mut self.session,
+                users: &self.users,
+                history: &[],
+                env: &mut self.env,
+                registry: &self.registry,
+                programs: &self.programs,
+                processes: &mut self.processes,
+                executed: vec![],
+            };
+            shell::run(line, &mut ctx).errors.iter().map(|error| render::strip_styles(error)).collect()
+        }
+
+        fn exists(&self, path: &str) -> bool {
+            VfsPath::parse(path, &VfsPath::root(), DEFAULT_USER).and_then(|path| self.fs.lookup(&path)).is_ok()
+        }
+
+        /// Adds a node owned by `owner` with the given permissions, under a directory that already exists
+        fn add(&mut self, path: &str, kind: NodeKind, owner: &str, permissions: &str) {
+            let path = VfsPath::parse(path, &VfsPath::root(), owner).unwrap();
+            let parent = self.fs.lookup_dir(&path.parent().unwrap()).unwrap();
+            let id = self.fs.create(parent, path.file_name().unwrap(), kind, owner).unwrap();
+            self.fs.set_permissions(id, Permissions::parse(permissions).unwrap());
+        }
+
+        /// `/files/shared`, a directory kai lets everyone read, holding a file only kai may read
+        fn with_shared_directory(mut self) -> Self {
+            self.add("/files/shared", NodeKind::Directory { children: vec![] }, "kai", "rwxr-x");
+            self.add("/files/shared/open.txt", NodeKind::File { content: String::from("hi") }, "kai", "rw-r--");
+            self.add("/files/shared/secret.txt", NodeKind::File { content: String::from("moonlight") }, "kai", "rw----");
+            self
+        }
+    }
+
+    #[test]
+    fn cp_copies_what_the_user_may_read() {
+        let mut console = TestConsole::new().with_shared_directory();
+        assert!(console.run("cp /files/shared/open.txt ~/open.txt").is_empty());
+        assert!(console.exists("~/open.txt"));
+        assert_eq!(console.run("cp /files/shared/secret.txt ~/secret.txt"), ["! /files/shared/secret.txt: permission denied"]);
+
+        console.run("mkdir /files/mine; echo text > /files/mine/a.txt");
+        assert!(console.run("cp -r /files/mine ~/mine").is_empty());
+        assert!(console.exists("~/mine/a.txt"));
+    }
+
+    #[test]
+    fn cp_r_checks_everything_it_copies() {
+        let mut console = TestConsole::new().with_shared_directory();
+        assert_eq!(console.run("cp -r /files/shared ~/shared"), ["! /files/shared/secret.txt: permission denied"]);
+        assert!(!console.exists("~/shared"));
+
+        // admin only programs could be run from the copy, which would belong to the player
+        assert!(!console.run("cp -r /programs ~/programs").is_empty());
+        assert!(!console.exists("~/programs"));
+        assert!(!console.run("cp /programs/create_server.exe ~/server.exe").is_empty());
+    }
+
+    #[test]
+    fn rm_r_needs_write_access_to_every_directory() {
+        let mut console = TestConsole::new().with_shared_directory();
+        assert_eq!(console.run("rm -r /files/shared"), ["! /files/shared: permission denied"]);
+        assert!(console.exists("/files/shared/secret.txt"));
+
+        console.run("mkdir ~/mine ~/mine/inner");
+        console.run("echo text > ~/mine/inner/a.txt");
+        assert!(console.run("rm -r ~/mine").is_empty());
+        assert!(!console.exists("~/mine"));
+    }
+
+    #[test]
+    fn find_only_starts_where_the_user_may_go() {
+        let mut console = TestConsole::new();
+        assert_eq!(console.run("find /users/kai/todo.txt"), ["! /users/kai: permission denied"]);
+        assert!(console.run("find /files -type f -name *.txt").is_empty());
+    }
+
+    #[test]
+    fn find_options_need_values() {
+        let mut console = TestConsole::new();
+        assert_eq!(console.run("find /files -name"), ["! -name needs a value. usage: find [dir] [-name <pattern>] [-type f|d]"]);
+        assert_eq!(console.run("find -type"), ["! -type needs a value. usage: find [dir] [-name <pattern>] [-type f|d]"]);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::commands::{ArgKind, CommandRegistry};
+use crate::programs;
+use crate::shell::ShellEnv;
+use crate::vfs::{Access, NodeId, NodeKind, Vfs, VfsPath};
+
+
+
+// candidate lists are packed into lines about this wide when printed to the scrollback
+const CANDIDATE_LINE_WIDTH: usize = 40;
+
+/// The result of pressing tab: text to insert at the cursor and, when it was ambiguous, what matched
+pub struct Completion {
+    pub insert: String,
+    pub candidates: Vec<String>,
+}
+
+/// Completes the word that ends at the cursor. The first word completes to a command or a program on $PATH,
+/// later words to whatever the command says its argument is
+pub fn complete(before_cursor: &str, registry: &CommandRegistry, fs: &Vfs, env: &ShellEnv, cwd: NodeId, user: &str) -> Completion {
+    // only the command after the last pipe matters
+    let before_cursor = before_cursor.rsplit('|').next().unwrap_or("");
+    // the whitespace can be more than one byte, like the no-break space option+space types
+    let word_start = before_cursor.char_indices().rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let word = &before_cursor[word_start..];
+    let previous: Vec<&str> = before_cursor[..word_start].split_whitespace().collect();
+
+    let kind = match previous.first() {
+        None => ArgKind::Command,
+        Some(name) => match registry.get(name) {
+            Some(command) => command.arg_kind(previous.len() - 1),
+            None => ArgKind::Path,
+        },
+    };
+
+    // only the part after the last slash is being completed
+    let (dir_part, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+
+    let mut candidates: Vec<String> = match kind {
+        ArgKind::Command => {
+            let programs = programs::search_path(fs, env, &fs.path_of(cwd), user).into_iter()
+                .filter(|dir| fs.check_access(*dir, user, Access::READ | Access::EXECUTE).is_ok())
+                .flat_map(|dir| fs.children(dir))
+                .filter_map(|id| fs.get(*id))
+                .filter(|node| matches!(node.kind, NodeKind::Program))
+                .map(|node| node.name.clone());
+            registry.iter().map(|command| String::from(command.name())).chain(programs).collect()
+        }
+        ArgKind::Path | ArgKind::Directory => match find_dir(fs, dir_part, cwd, user) {
+            Some(dir) => fs.children(dir).iter()
+                .filter_map(|id| fs.get(*id))
+                .filter(|node| kind == ArgKind::Path || node.is_dir())
+                .map(|node| if node.is_dir() { format!("{}/", node.name) } else { node.name.clone() })
+                .collect(),
+            None => vec![],
+        },
+        ArgKind::Text => vec![],
+    };
+    candidates.retain(|candidate| candidate.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+
+    let shared = common_prefix(&candidates);
+    let mut insert = String::from(shared.get(prefix.len()..).unwrap_or(""));
+    if candidates.len() == 1 && !insert.ends_with('/') {
+        insert.push(' ');
+    }
+
+    if candidates.len() <= 1 {
+        candidates.clear();
+    }
+
+    Completion { insert, candidates }
+}
+
+/// Packs candidate names into lines for the scrollback
+pub fn format_candidates(candidates: &[String]) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for candidate in candidates {
+        if !line.is_empty() && line.len() + candidate.len() + 2 > CANDIDATE_LINE_WIDTH {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push_str("  ");
+        }
+        line.push_str(candidate);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The directory whose children complete the word, if `user` could list it with ls
+fn find_dir(fs: &Vfs, dir_part: &str, cwd: NodeId, user: &str) -> Option<NodeId> {
+    let dir = match dir_part {
+        "" => cwd,
+        _ => fs.lookup_dir(&VfsPath::parse(dir_part, &fs.path_of(cwd), user).ok()?).ok()?,
+    };
+    fs.check_access(dir, user, Access::READ | Access::EXECUTE).ok()?;
+    Some(dir)
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = match candidates.first() {
+        Some(first) => first.clone(),
+        None => { return String::new(); }
+    };
+    for candidate in &candidates[1..] {
+        let shared = prefix.chars().zip(candidate.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        prefix.truncate(shared);
+    }
+    prefix
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use super::*;
+    use crate::commands::CommandsPlugin;
+    use crate::vfs::{self, DEFAULT_USER};
+
+    fn complete_on_built_in_tree(before_cursor: &str, env: &ShellEnv) -> Completion {
+        let mut app = App::new();
+        app.add_plugins(CommandsPlugin);
+        let registry = app.world.remove_resource::<CommandRegistry>().unwrap();
+        let fs = Vfs::from_directory(&vfs::built_in_definition());
+        complete(before_cursor, &registry, &fs, env, Vfs::ROOT, DEFAULT_USER)
+    }
+
+    #[test]
+    fn words_split_on_multi_byte_whitespace() {
+        let env = ShellEnv::default();
+        assert_eq!(complete_on_built_in_tree("cat\u{a0}/fil", &env).insert, "es/");
+        assert_eq!(complete_on_built_in_tree("cat /files/data.txt\u{3000}/us", &env).insert, "ers/");
+        assert_eq!(complete_on_built_in_tree("cat\u{a0}", &env).candidates.len(), 4);
+    }
+
+    #[test]
+    fn commands_complete_to_programs_on_the_path() {
+        let mut env = ShellEnv::default();
+        assert_eq!(complete_on_built_in_tree("countd", &env).insert, "own.exe ");
+        assert_eq!(complete_on_built_in_tree("c", &env).candidates.iter().filter(|name| name.ends_with(".exe")).count(), 2);
+
+        env.set("PATH", "/files");
+        assert_eq!(complete_on_built_in_tree("countd", &env).insert, "");
+    }
+}
@@ -1,14 +1,14 @@
 use std::f32::consts::PI;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use bevy::{core_pipeline::core_3d::graph::input, prelude::*, render::render_asset::RenderAssetUsages, transform::commands};
 use bevy_rapier3d::parry::simba::scalar::SupersetOf;
 // use bevy_rapier3d::rapier::dynamics::RigidBody;
 use bevy_ui::prelude::*;
 use bevy::math::vec3;
-use meshtext::{MeshGenerator, MeshText, TextSection};
 use bevy_rapier3d::{parry::query::Ray, prelude::*};
 use bevy::input::mouse::MouseWheel;
 use bevy::input::mouse::MouseScrollUnit;
+use bevy::window::ReceivedCharacter;
 use serde::{Deserialize, Serialize};
 use bevy_renet::{
     client_connected,
@@ -26,34 +26,315 @@ use renet::{
 use std::time::SystemTime;
 use std::{net::UdpSocket};
 use crate::game;
-
-
-
+use crate::commands::{CommandContext, CommandOutput, CommandRegistry, ExecutedCommand, TerminalAction};
+use crate::history::{self, CommandHistory};
+use crate::process::{self, ProcessTable};
+use crate::programs::ProgramRegistry;
+use crate::render::{self, Blink, Row, TermColor, TerminalFont, TerminalPalette};
+use crate::completion;
+use crate::shell::{self, ShellEnv};
+use crate::users::{Session, Users};
+use crate::editor::{EditorEvent, FileEditor, OpenEditor};
+use crate::events::CommandExecuted;
+use crate::pager::{OpenPager, Pager};
+use crate::vfs::{NodeId, Vfs};
+
+
+
+/// The console's id, which its saved history is named after
 #[derive(Component)]
 pub struct ActiveTerminal {
     pub id: String
 }
 
+/// The console the player is using, the only one that gets keyboard input
+#[derive(Resource, Default)]
+pub struct FocusedConsole(pub Option<Entity>);
+
+// the first thing every console prints
+const BANNER: &str = "universal instruction terminal v0.2.3";
+
 #[derive(Component)]
 pub struct ConsoleTerminal;
 
-#[derive(Resource)]
+// lines kept in the scrollback, the oldest are dropped past this
+const SCROLLBACK_LINES: usize = 1000;
+
+/// How big a terminal screen is. `rows` counts the prompt
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct TerminalGeometry {
+    pub rows: usize,
+    pub columns: usize,
+    pub line_spacing: f32, // in the screen's units
+}
+
+impl Default for TerminalGeometry {
+    fn default() -> Self {
+        TerminalGeometry { rows: 14, columns: 48, line_spacing: 0.05 }
+    }
+}
+
+/// A console's scrollback. Lines are stored as printed and wrapped to the screen when drawn
+#[derive(Component)]
 pub struct Terminal {
-    pub text: Vec<String>,
-    pub upper: usize,
-    pub lower: usize,
+    lines: VecDeque<String>,
+    scroll: usize, // screen rows scrolled back from the newest output, 0 follows it
 }
 
-#[derive(Resource)]
+impl Terminal {
+    pub fn new(lines: Vec<String>) -> Self {
+        let mut terminal = Terminal { lines: VecDeque::new(), scroll: 0 };
+        terminal.extend(lines);
+        terminal
+    }
+
+    /// Adds a line at the bottom and jumps back down to it
+    pub fn push(&mut self, line: impl Into<String>) {
+        // lines with newlines in them are split so every entry is one printed line
+        for line in line.into().split('\n') {
+            if self.lines.len() == SCROLLBACK_LINES {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(String::from(line));
+        }
+        self.scroll = 0;
+    }
+
+    pub fn extend(&mut self, lines: impl IntoIterator<Item = String>) {
+        for line in lines {
+            self.push(line);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.scroll = 0;
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &String> {
+        self.lines.iter()
+    }
+
+    /// Scrolls by `rows`, positive goes back through older output. Stops at the newest line and the oldest one
+    pub fn scroll_by(&mut self, rows: isize, visible_rows: usize, columns: usize) {
+        let total: usize = self.lines.iter().map(|line| render::wrap(line, columns).len()).sum();
+        let max = total.saturating_sub(visible_rows);
+        self.scroll = self.scroll.saturating_add_signed(rows).min(max);
+    }
+
+    /// The wrapped rows that fit on a screen `rows` high, oldest first. Fewer if there is not that much output
+    pub fn screen(&self, rows: usize, columns: usize) -> Vec<Row> {
+        // wrap from the bottom up, only as far as the screen reaches
+        let mut wrapped: Vec<Row> = vec![];
+        for line in self.lines.iter().rev() {
+            if wrapped.len() >= self.scroll + rows {
+                break;
+            }
+            wrapped.extend(render::wrap(line, columns).into_iter().rev());
+        }
+
+        let skip = self.scroll.min(wrapped.len().saturating_sub(rows));
+        wrapped.into_iter().skip(skip).take(rows).rev().collect()
+    }
+}
+
+/// What is typed on a console's prompt
+#[derive(Component, Default)]
 pub struct CurrentCommand {
-    pub text: String
+    pub text: String,
+    pub cursor: usize, // in chars, 0..=text.chars().count()
+}
+
+impl CurrentCommand {
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text.char_indices().nth(cursor).map_or(self.text.len(), |(i, _)| i)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.cursor);
+        self.text.insert(i, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let i = self.byte_index(self.cursor);
+            self.text.remove(i);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.len() {
+            let i = self.byte_index(self.cursor);
+            self.text.remove(i);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.len();
+    }
+
+    /// moves to the start of the previous word
+    pub fn word_left(&mut self) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut cursor = self.cursor;
+        while cursor > 0 && chars[cursor - 1].is_whitespace() { cursor -= 1; }
+        while cursor > 0 && !chars[cursor - 1].is_whitespace() { cursor -= 1; }
+        self.cursor = cursor;
+    }
+
+    /// moves to the end of the next word
+    pub fn word_right(&mut self) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut cursor = self.cursor;
+        while cursor < chars.len() && chars[cursor].is_whitespace() { cursor += 1; }
+        while cursor < chars.len() && !chars[cursor].is_whitespace() { cursor += 1; }
+        self.cursor = cursor;
+    }
+
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.byte_index(self.cursor)]
+    }
+
+    /// replaces the whole line and puts the cursor at the end
+    pub fn set(&mut self, text: String) {
+        self.text = text;
+        self.cursor = self.len();
+    }
+
+    /// empties the line and returns what was typed
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    /// the line as it is drawn on the prompt, with a marker at the cursor
+    pub fn display(&self) -> String {
+        let mut line = self.text.clone();
+        line.insert(self.byte_index(self.cursor), '|');
+        line
+    }
+}
+
+const KEY_REPEAT_DELAY: f32 = 0.4;
+const KEY_REPEAT_RATE: f32 = 0.035;
+
+// editing keys that repeat while held. typed characters are already repeated by the os
+const EDITING_KEYS: [KeyCode; 8] = [
+    KeyCode::Backspace,
+    KeyCode::Delete,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::Home,
+    KeyCode::End,
+];
+
+#[derive(Default)]
+pub struct KeyRepeat {
+    held: Option<KeyCode>,
+    timer: Timer,
+}
+
+impl KeyRepeat {
+    /// returns the keys that should fire this frame, either freshly pressed or repeating
+    fn update(&mut self, input: &ButtonInput<KeyCode>, time: &Time, keys: &[KeyCode]) -> Vec<KeyCode> {
+        let mut fired = vec![];
+        for key in keys {
+            if input.just_pressed(*key) {
+                fired.push(*key);
+                self.held = Some(*key);
+                self.timer = Timer::from_seconds(KEY_REPEAT_DELAY, TimerMode::Once);
+            }
+        }
+
+        if fired.is_empty() {
+            if let Some(key) = self.held {
+                if input.pressed(key) {
+                    self.timer.tick(time.delta());
+                    if self.timer.finished() {
+                        fired.push(key);
+                        self.timer = Timer::from_seconds(KEY_REPEAT_RATE, TimerMode::Once);
+                    }
+                } else {
+                    self.held = None;
+                }
+            }
+        }
+
+        fired
+    }
 }
 
 #[derive(Component)]
 pub struct TerminalScreen;
 
+/// One row of text on a console's screen, with what was last drawn there.
+/// Each style in the row is drawn by a child entity with that style's material
 #[derive(Component)]
-pub struct ConsoleText;
+pub struct ConsoleText {
+    pub console: Entity,
+    pub row: usize,
+    shown: Option<Row>,
+}
+
+impl ConsoleText {
+    pub fn new(console: Entity, row: usize) -> Self {
+        ConsoleText { console, row, shown: None }
+    }
+}
+
+/// Everything that belongs to one console
+#[derive(Bundle)]
+pub struct ConsoleBundle {
+    console: ConsoleTerminal,
+    id: ActiveTerminal,
+    terminal: Terminal,
+    current_command: CurrentCommand,
+    current_directory: CurrentDirectory,
+    history: CommandHistory,
+    open_editor: OpenEditor,
+    open_pager: OpenPager,
+    env: ShellEnv,
+    processes: ProcessTable,
+    session: Session,
+}
+
+impl ConsoleBundle {
+    pub fn new(id: &str) -> Self {
+        ConsoleBundle {
+            console: ConsoleTerminal,
+            id: ActiveTerminal { id: String::from(id) },
+            terminal: Terminal::new(vec![String::from(BANNER)]),
+            current_command: CurrentCommand::default(),
+            current_directory: CurrentDirectory::default(),
+            history: CommandHistory::load(id),
+            open_editor: OpenEditor::default(),
+            open_pager: OpenPager::default(),
+            env: ShellEnv::default(),
+            processes: ProcessTable::default(),
+            session: Session::default(),
+        }
+    }
+}
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 pub enum ConsoleState {
@@ -74,48 +355,97 @@ impl Plugin for ConsolePlugin {
     }
 }
 
-fn access_terminal(
-    input: Res<ButtonInput<KeyCode>>,
-) {
-    if input.just_pressed(KeyCode::KeyF) { // and check if in front of console
-        // move camera to be looking right at console screen
+/// The rows on a console's screen: output, then the prompt, or the editor if one is open
+fn screen_rows(
+    terminal: &Terminal,
+    current_command: &CurrentCommand,
+    history: &CommandHistory,
+    open_editor: &mut OpenEditor,
+    open_pager: &OpenPager,
+    processes: &ProcessTable,
+    session: &Session,
+    geometry: &TerminalGeometry,
+) -> Vec<Row> {
+    // an open editor takes over the whole screen
+    if let Some(editor) = open_editor.0.as_mut() {
+        return editor.render(geometry.rows).iter().map(|line| render::parse(line)).collect();
     }
+    if let Some(pager) = &open_pager.0 {
+        return pager.render(geometry.rows, geometry.columns);
+    }
+
+    let command_line_string = match history.search_prompt() {
+        _ if processes.foreground().is_some() => String::new(),
+        _ if session.prompt.is_some() => session.prompt.as_ref().map(|prompt| prompt.label()).unwrap_or_default(),
+        Some(prompt) => prompt,
+        // root gets a # prompt, as in unix
+        None => format!("{} {}", if session.is_root() { "#" } else { ">" }, current_command.display()),
+    };
+
+    // a long prompt wraps onto more rows and pushes the output up
+    let mut prompt = render::wrap(&command_line_string, geometry.columns);
+    let prompt_rows = prompt.len().min(geometry.rows);
+    prompt.drain(..prompt.len() - prompt_rows);
+
+    let output_rows = geometry.rows - prompt_rows;
+    let mut rows = terminal.screen(output_rows, geometry.columns);
+    rows.resize(output_rows, vec![]);
+    rows.extend(prompt);
+    rows
 }
 
 pub fn update_terminal(
-    terminal: ResMut<Terminal>,
-    current_command: ResMut<CurrentCommand>,
-    mut terminal_child_query: Query<(Entity, &mut Handle<Mesh>), With<ConsoleText>>,
-    mut terminal_screen_query: Query<Entity, (With<TerminalScreen>, Without<ConsoleText>)>,
+    mut console_query: Query<(Entity, Ref<Terminal>, Ref<CurrentCommand>, Ref<CommandHistory>, &mut OpenEditor, Ref<OpenPager>, Ref<ProcessTable>, Ref<Session>), With<ConsoleTerminal>>,
+    geometry: Res<TerminalGeometry>,
+    mut font: ResMut<TerminalFont>,
+    mut palette: ResMut<TerminalPalette>,
+    mut terminal_child_query: Query<(Entity, &mut ConsoleText)>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-
-    let mut text_list: Vec<&str> = vec![];
-    for line in terminal.text.iter() {
-        text_list.push(&line);
-    }
-
-    if text_list.len() >= terminal.upper {
-        let l = text_list.len();
-        text_list = text_list[(l-terminal.upper)..((l-terminal.upper)+13)].to_vec();
-        let l2 = text_list.len();
-    } else {
-        let leftover = 14 - text_list.len();
-
-        for x in 0..leftover {
-            text_list.push(".");
+    let undrawn: HashSet<Entity> = terminal_child_query.iter()
+        .filter(|(_, line)| line.shown.is_none())
+        .map(|(_, line)| line.console)
+        .collect();
+
+    // nothing on a screen can have changed unless one of its console's parts did
+    let mut screens: HashMap<Entity, Vec<Row>> = HashMap::new();
+    for (console, terminal, current_command, history, mut open_editor, open_pager, processes, session) in console_query.iter_mut() {
+        let changed = terminal.is_changed() || current_command.is_changed() || history.is_changed() || open_editor.is_changed()
+            || open_pager.is_changed() || processes.is_changed() || session.is_changed() || geometry.is_changed()
+            || undrawn.contains(&console);
+        if changed {
+            let rows = screen_rows(&terminal, &current_command, &history, &mut open_editor, &open_pager, &processes, &session, &geometry);
+            screens.insert(console, rows);
         }
     }
 
+    // only rows whose text changed are rebuilt. their old spans are despawned, which frees the old meshes
+    let empty = vec![];
+    for (entity, mut line) in terminal_child_query.iter_mut() {
+        let screen = match screens.get(&line.console) {
+            Some(screen) => screen,
+            None => continue,
+        };
+        let row = screen.get(line.row).unwrap_or(&empty);
+        if line.shown.as_ref() == Some(row) {
+            continue;
+        }
 
-    let mut command_line_string = String::from("> ");
-    command_line_string.push_str(&current_command.text);
-    text_list.insert(13, &command_line_string);
-
-    for (i, (mut entity, mut mesh) ) in terminal_child_query.iter_mut().enumerate() {
-        *mesh = meshes.add(get_text_mesh(text_list[i]));
+        commands.entity(entity).despawn_descendants();
+        for (style, cells) in render::spans(row) {
+            let span = commands.spawn(PbrBundle {
+                mesh: meshes.add(font.cells_mesh(&cells)),
+                material: palette.material(style, &mut materials),
+                ..default()
+            }).id();
+            if style.blink {
+                commands.entity(span).insert(Blink);
+            }
+            commands.entity(entity).add_child(span);
+        }
+        line.shown = Some(row.clone());
     }
 }
 
@@ -125,8 +455,7 @@ pub fn spawn_console(
     id: String,
     asset_server: & Res<AssetServer>,
     mut commands: &mut Commands,
-    mut meshes: &mut ResMut<Assets<Mesh>>,
-    mut materials: &mut ResMut<Assets<StandardMaterial>>,
+    geometry: &TerminalGeometry,
 ) {
 
     let console_scene = asset_server.load("objects/console.gltf#Scene0");
@@ -152,7 +481,7 @@ pub fn spawn_console(
             game::InteractionType::Console
         ));
     })
-    .insert(ConsoleTerminal)
+    .insert(ConsoleBundle::new(&id))
     .id();
     // .insert(RigidBody::Dynamic)
     // .insert(Collider::from_bevy_mesh(cmesh, &ComputedColliderShape::ConvexHull))
@@ -169,8 +498,7 @@ pub fn spawn_console(
     // .insert(TransformBundle::from_transform(transform))
     // .id();
     
-    let mesh = get_text_mesh("");
-    let scale = vec3(0.05, 0.05, 0.05);
+    let scale = Vec3::splat(TEXT_SCALE);
 
     // create the screen element to attatch the text children to
     let screen = commands.spawn(PbrBundle {
@@ -187,10 +515,8 @@ pub fn spawn_console(
     }).insert(TerminalScreen)
     .id();
 
-    // create the default bundle for the text
-    let bundle = PbrBundle {
-        mesh: meshes.add(mesh),
-        material: materials.add(Color::rgb(0.1, 1.0, 0.1)),
+    // create the default bundle for the text, the meshes are children added when a row is drawn
+    let bundle = SpatialBundle {
         transform: Transform {
             translation: vec3(0.0, 1.6, 0.0), 
             scale,
@@ -200,45 +526,32 @@ pub fn spawn_console(
         ..Default::default()
     };
 
-    for i in 0..13 {
+    // one text entity per row, the prompt is drawn on whichever rows it needs at the bottom
+    for i in 0..geometry.rows {
         let mut b = bundle.clone();
-        let new_bundle = get_text_pos(&mut b, i as usize);
-        let line = commands.spawn(new_bundle).insert(ConsoleText).id();
+        let new_bundle = get_text_pos(&mut b, i, geometry.line_spacing);
+        let line = commands.spawn(new_bundle).insert(ConsoleText::new(console, i)).id();
         commands.entity(screen).add_child(line);
     }
 
-    let command_bundle = get_text_pos(&mut bundle.clone(), 13);
-    let command_line = commands.spawn(command_bundle).insert(ConsoleText).id();
-    commands.entity(screen).add_child(command_line);
-
     commands.entity(console).add_child(screen);
 
 }
 
-fn get_text_mesh(
-    text: &str
-) -> Mesh {
-    let font_data = include_bytes!("../assets/fonts/KodeMono-Regular.ttf");
-    let mut generator = MeshGenerator::new(font_data);
-    let transform = Mat4::from_scale(Vec3::new(1f32, 1f32, 0.2f32)).to_cols_array();
-    let text_mesh: MeshText = generator
-        .generate_section(&text.to_string(), false, Some(&transform))
-        .unwrap();
-
-    let vertices = text_mesh.vertices;
-    let positions: Vec<[f32; 3]> = vertices.chunks(3).map(|c| [c[0], c[1], c[2]]).collect();
-    let uvs = vec![[0f32, 0f32]; positions.len()];
+// how big the text is and where its first row sits on the screen
+const TEXT_SCALE: f32 = 0.05;
+const TEXT_TOP: f32 = 1.6;
 
-    let mut mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList, RenderAssetUsages::all());
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.compute_flat_normals();
-
-    return mesh;
+/// The middle of a console's text, in its screen's space. Rows run down from the top
+/// and, turned round to face out of the screen, text runs along -x
+pub fn screen_center(geometry: &TerminalGeometry, advance: f32) -> Vec3 {
+    let width = geometry.columns as f32 * advance * TEXT_SCALE;
+    let height = geometry.rows as f32 * geometry.line_spacing;
+    vec3(-width / 2.0, TEXT_TOP - height / 2.0, 0.0)
 }
 
-fn get_text_pos(bundle: &mut PbrBundle, index: usize) -> PbrBundle {
-    let y = 1.6 - (0.05*index as f32);
+fn get_text_pos(bundle: &mut SpatialBundle, index: usize, line_spacing: f32) -> SpatialBundle {
+    let y = TEXT_TOP - (line_spacing*index as f32);
     // println!("{}", y);
     let mut new_bundle = bundle.clone();
     new_bundle.transform.translation = vec3(0.0, y, 0.0);
@@ -254,37 +567,125 @@ fn get_text_pos(bundle: &mut PbrBundle, index: usize) -> PbrBundle {
 //            TERMINAL STRUCTURE AND NAVIGATION
 
 
-#[derive(Resource, Deserialize)]
+/// The live filesystem every console works on, with the definition it was last built or merged from
+#[derive(Resource)]
 pub struct GameDirectory {
-    pub root: Directory
+    pub fs: Vfs,
+    definition: Directory,
 }
 
-#[derive(Resource)]
-pub struct CurrentDirectory(pub Directory);
+impl GameDirectory {
+    pub fn new(definition: Directory) -> Self {
+        GameDirectory { fs: Vfs::from_directory(&definition), definition }
+    }
 
-#[derive(Clone, Deserialize)]
+    /// Rebuilds a saved filesystem along with the definition it had been merged from
+    pub fn restore(tree: &Directory, definition: Directory) -> Self {
+        GameDirectory { fs: Vfs::from_directory(tree), definition }
+    }
+
+    pub fn definition(&self) -> &Directory {
+        &self.definition
+    }
+
+    /// Applies a new version of the definition to the live filesystem
+    pub fn merge_definition(&mut self, definition: Directory) {
+        self.fs.merge_definition(&self.definition, &definition);
+        self.definition = definition;
+    }
+}
+
+/// A console's working directory
+#[derive(Component)]
+pub struct CurrentDirectory(pub NodeId);
+
+impl Default for CurrentDirectory {
+    fn default() -> Self {
+        CurrentDirectory(Vfs::ROOT)
+    }
+}
+
+// the tree below is the serialized form of the filesystem, which `Vfs::from_directory` builds the arena from
+// and `Vfs::to_directory` turns back into
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Node {
     Directory(Directory),
     Program(Program),
     File(File)
 }
 
-#[derive(Clone, Deserialize)]
+impl Node {
+    /// the last part of the node's name, which is what the player sees and types
+    pub fn display_name(&self) -> String {
+        let name = match self {
+            Node::Directory(dir) => &dir.name,
+            Node::Program(program) => &program.name,
+            Node::File(file) => &file.name,
+        };
+        String::from(name.split("/").filter(|part| !part.is_empty()).last().unwrap_or(""))
+    }
+
+    /// the owner and `rwxr-x` style permissions set in the tree, if any
+    pub fn access(&self) -> (Option<&str>, Option<&str>) {
+        let (owner, permissions) = match self {
+            Node::Directory(dir) => (&dir.owner, &dir.permissions),
+            Node::Program(program) => (&program.owner, &program.permissions),
+            Node::File(file) => (&file.owner, &file.permissions),
+        };
+        (owner.as_deref(), permissions.as_deref())
+    }
+
+    /// when the node was created and last modified, if the tree was saved from a running game
+    pub fn times(&self) -> (Option<u64>, Option<u64>) {
+        match self {
+            Node::Directory(dir) => (dir.created, dir.modified),
+            Node::Program(program) => (program.created, program.modified),
+            Node::File(file) => (file.created, file.modified),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Program {
-    name: String
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>, // unix seconds, only saves have these
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct File {
-    name: String,
-    content: String
+    pub name: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>, // unix seconds, only saves have these
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
 }
 
 // A node representing a directory
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Directory {
     pub name: String,
     pub children: HashMap<String, Node>, // Map of child directory names to Directory structs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>, // unix seconds, only saves have these
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
 }
 
 impl Directory {
@@ -292,6 +693,10 @@ impl Directory {
         Directory {
             name,
             children: HashMap::new(),
+            owner: None,
+            permissions: None,
+            created: None,
+            modified: None,
         }
     }
 
@@ -311,7 +716,7 @@ impl Directory {
         if self.children.contains_key(&name) {
             Err(format!("name '{}' already exists", &name))
         } else {
-            let mut new_program = Program { name: name.clone() };
+            let mut new_program = Program { name: name.clone(), owner: None, permissions: None, created: None, modified: None };
             self.children.insert(name, Node::Program(new_program));
             Ok(())
         }
@@ -322,7 +727,7 @@ impl Directory {
         if self.children.contains_key(&name) {
             Err(format!("name '{}' already exists", &name))
         } else {
-            let mut new_program = File { name: name.clone(), content: String::from("") };
+            let mut new_program = File { name: name.clone(), content: String::from(""), owner: None, permissions: None, created: None, modified: None };
             self.children.insert(name, Node::File(new_program));
             Ok(())
         }
@@ -349,36 +754,6 @@ impl Directory {
         // return child;
     }
 
-    pub fn find_child_dir(&mut self, name: &str) -> Option<Directory> {
-        if self.children.clone().contains_key(name) {
-            match self.clone().get_child(name) {
-                Some(node) => {
-                    match node {
-                        Node::Directory(dir) => {
-                            return Some(dir.clone());
-                        }
-                        _ => {}
-                    }
-                },
-                None => {},
-            }
-            
-        }
-
-        for (_, child) in &mut self.children {
-            match child {
-                Node::Directory(dir) => {
-                    return dir.find_child_dir(name);
-                }
-                _ => {
-
-                }
-            }
-        }
-
-        None
-    }
-
     pub fn delete_child(&mut self, name: &str) -> Result<(), String> {
         if let Some(_child) = self.children.remove(name) {
             Ok(())
@@ -386,74 +761,9 @@ impl Directory {
             Err(format!("Directory with name '{}' not found", name))
         }
     }
-
-    pub fn cd(&mut self, path: &str) -> Result<&Directory, String> {
-        let mut current_dir = self;
-        let mut dir_name = format!("{}{}/", current_dir.name, path);
-        if dir_name.ends_with("//") { dir_name.pop(); }
-        println!("{dir_name}");
-        let cd = current_dir.get_child(&dir_name).ok_or_else(|| "could not find dir")?;
-
-        match cd {
-            Node::Directory(dir) => {
-                Ok(dir)
-            }
-            _ => {
-                Err(String::from("! not a valid directory"))
-            }
-        }
-
-        
-    }
-
-    pub fn ls(&self) -> (Vec<String>, Vec<String>) {
-        let mut names = vec![];
-        let mut lengths = vec![];
-        if self.children.is_empty() {
-            names.push(String::from("~ empty"))
-        } else {
-            for child in self.children.clone() {
-                match child.1 {
-                    Node::Directory(dir) => {
-                        let mut n = child.0.clone();
-                        _=n.pop();
-                        let v = n.split("/").last().unwrap();
-        
-                        names.push(String::from(v));
-                        lengths.push(dir.children.len().to_string());
-                    }
-                    Node::Program(program) => {
-                        let mut n = program.name.clone();
-                        // _=n.pop();
-                        let v = n.split("/").last().unwrap();
-                        names.push(String::from(v));
-                        lengths.push(String::from("!"));
-                    }
-                    Node::File(file) => {
-                        let mut n = file.name.clone();
-                        // _=n.pop();
-                        let v = n.split("/").last().unwrap();
-                        names.push(String::from(v));
-                        lengths.push(String::from("#"));
-                    }
-                    _ => {}
-                }
-
-            }
-        }
-        return (names, lengths);
-    }
 }
 
 
-fn terminal_list(dir: Directory) -> (Vec<String>, Vec<String>) {
-    return dir.ls();
-} 
-
-fn make_hello() -> String {
-    return String::from("hello, my name is sumi :3");
-}
-
 // very happy with this
 // need to add the root to the world resources
 // need to format the outputs
@@ -464,210 +774,268 @@ fn make_hello() -> String {
 
 //                   HANDLE TERMINAL INPUTS AND UPDATE SCREEN
 
+/// The parts of a console a line typed at its prompt works on. Borrowed from the console's components in the game,
+/// and from plain values in the headless shell
+pub struct LineContext<'a> {
+    pub fs: &'a mut Vfs,
+    pub current_directory: &'a mut CurrentDirectory,
+    pub history: &'a mut CommandHistory,
+    pub session: &'a mut Session,
+    pub env: &'a mut ShellEnv,
+    pub processes: &'a mut ProcessTable,
+    pub users: &'a Users,
+    pub registry: &'a CommandRegistry,
+    pub programs: &'a ProgramRegistry,
+}
+
+/// Records a line entered at a prompt and runs it. Nothing here reads keys or draws, the caller shows the output
+/// and carries out its actions. Also returns every command that ran, for [`CommandExecuted`]
+pub fn run_line(line: &str, ctx: LineContext) -> (CommandOutput, Vec<ExecutedCommand>) {
+    ctx.history.record(line);
+
+    let mut command_ctx = CommandContext {
+        args: vec![],
+        stdin: vec![],
+        fs: ctx.fs,
+        current_directory: ctx.current_directory,
+        session: ctx.session,
+        users: ctx.users,
+        history: &ctx.history.entries,
+        env: ctx.env,
+        registry: ctx.registry,
+        programs: ctx.programs,
+        processes: ctx.processes,
+        executed: vec![],
+    };
+    let output = shell::run(line, &mut command_ctx);
+    (output, command_ctx.executed)
+}
+
 
 pub fn use_console(
-    mut terminal: ResMut<Terminal>,
-    mut current_command: ResMut<CurrentCommand>,
+    mut console_query: Query<(
+        &mut Terminal,
+        &mut CurrentCommand,
+        &mut CurrentDirectory,
+        &mut CommandHistory,
+        &mut OpenEditor,
+        &mut OpenPager,
+        &mut ShellEnv,
+        &mut ProcessTable,
+        &mut Session,
+    ), With<ConsoleTerminal>>,
+    focused: Res<FocusedConsole>,
     mut root: ResMut<GameDirectory>,
-    mut current_directory: ResMut<CurrentDirectory>,
-    mut console_state: Res<State<ConsoleState>>,
+    registry: Res<CommandRegistry>,
+    programs: Res<ProgramRegistry>,
+    users: Res<Users>,
+    console_state: Res<State<ConsoleState>>,
     input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
     mut scroll_evr: EventReader<MouseWheel>,
+    mut character_evr: EventReader<ReceivedCharacter>,
     mut next_console_state: ResMut<NextState<ConsoleState>>,
-    mut commands: Commands,
+    mut executed_evw: EventWriter<CommandExecuted>,
+    mut key_repeat: Local<KeyRepeat>,
+    geometry: Res<TerminalGeometry>,
 ) {
+    // always drain the events so keys pressed while walking around
+    // (like the F used to open the terminal) do not end up on the prompt
+    let scroll: Vec<MouseWheel> = scroll_evr.read().cloned().collect();
+    let typed: Vec<char> = character_evr.read()
+        .flat_map(|event| event.char.chars().collect::<Vec<char>>())
+        .filter(|c| !c.is_control())
+        .collect();
+
     match console_state.get() {
         ConsoleState::IsUsingConsole => {
 
-            // upper cannot be lower that 13
-            // nor can it be above length of list
-            // list.len() = 15? 15-13
-            for event in scroll_evr.read() {
+            // input only goes to the console the player walked up to
+            let console = focused.0.and_then(|console| console_query.get_mut(console).ok());
+            let (mut terminal, mut current_command, mut current_directory, mut history, mut open_editor, mut open_pager, mut env, mut processes, mut session) = match console {
+                Some(console) => console,
+                None => { return; }
+            };
+
+            // the wheel moves one row per notch, up goes back through older output
+            let output_rows = geometry.rows.saturating_sub(1);
+            for event in scroll {
                 if event.y > 0.0 {
-                    println!("UP");
-                    let size = terminal.text.len();
-                    terminal.upper += 1;
-                    if terminal.upper >= size { terminal.upper = size }
-                } else {
-                    println!("DOWN");
-                    terminal.upper -= 1;
-                    if terminal.upper < 13 { terminal.upper = 13 }
+                    terminal.scroll_by(1, output_rows, geometry.columns);
+                } else if event.y < 0.0 {
+                    terminal.scroll_by(-1, output_rows, geometry.columns);
                 }
             }
 
+            let ctrl = input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+            let keys = key_repeat.update(&input, &time, &EDITING_KEYS);
 
+            // everything below only acts on input. a quiet frame must not touch the components mutably,
+            // which would mark them changed and have update_terminal redraw the screen every frame
+            if typed.is_empty() && keys.is_empty() && input.get_just_pressed().next().is_none() {
+                return;
+            }
 
-            for key in input.get_just_pressed() {
-                println!("{}", current_command.text);
-                if input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
-                    // shift is held
-                    for key in input.get_just_pressed() {
-                        match key {
-                            KeyCode::Digit0 => { current_command.text.push_str("!") }
-                            KeyCode::Digit1 => { current_command.text.push_str("@") }
-                            KeyCode::Digit2 => { current_command.text.push_str("#") }
-                            KeyCode::Digit3 => { current_command.text.push_str("$") }
-                            KeyCode::Digit4 => { current_command.text.push_str("%") }
-                            KeyCode::Digit5 => { current_command.text.push_str("^") }
-                            KeyCode::Digit6 => { current_command.text.push_str("&") }
-                            KeyCode::Digit7 => { current_command.text.push_str("*") }
-                            KeyCode::Digit8 => { current_command.text.push_str("(") }
-                            KeyCode::Digit9 => { current_command.text.push_str(")") }
-                            KeyCode::Slash => { current_command.text.push_str("?") }
-                            KeyCode::Comma => { current_command.text.push_str("<") }
-                            KeyCode::Period => { current_command.text.push_str(">") }
-                            KeyCode::Semicolon => { current_command.text.push_str(":") }
-                            KeyCode::Quote => { current_command.text.push_str("\"") }
-                            KeyCode::BracketLeft => { current_command.text.push_str("{") }
-                            KeyCode::BracketRight => { current_command.text.push_str("}") }
-                            KeyCode::Backslash => { current_command.text.push_str("|") }
-                            KeyCode::Minus => { current_command.text.push_str("_") }
-                            KeyCode::Equal => { current_command.text.push_str("+") }
-                            _ => {}
+            // the modes below are looked at before being borrowed mutably, which alone would mark them changed
+            if open_editor.0.is_some() {
+                if let Some(editor) = &mut open_editor.0 {
+                    match editor.handle_input(&typed, &keys, &input) {
+                        EditorEvent::Save => { editor.save(&mut root.fs); }
+                        EditorEvent::Quit => { open_editor.0 = None; }
+                        EditorEvent::None => {}
+                    }
+                }
+                return;
+            }
+
+            if open_pager.0.is_some() {
+                if let Some(pager) = &mut open_pager.0 {
+                    if !pager.handle_input(&typed, &keys, &input, geometry.rows, geometry.columns) {
+                        open_pager.0 = None;
+                    }
+                }
+                return;
+            }
+
+            // a foreground process has the terminal until it exits or ctrl+c stops it
+            if let Some(pid) = processes.foreground() {
+                if ctrl && input.just_pressed(KeyCode::KeyC) {
+                    processes.kill(pid);
+                    terminal.push(String::from("^C"));
+                    env.last_status = process::INTERRUPTED;
+                }
+                return;
+            }
+
+            // a login waiting for its password takes the typing, which is never drawn
+            if session.prompt.is_some() {
+                if let Some(prompt) = &mut session.prompt {
+                    prompt.input.extend(typed);
+                    if keys.contains(&KeyCode::Backspace) {
+                        prompt.input.pop();
+                    }
+                }
+                let label = session.prompt.as_ref().map(|prompt| prompt.label()).unwrap_or_default();
+
+                if (ctrl && input.just_pressed(KeyCode::KeyC)) || input.just_pressed(KeyCode::Escape) {
+                    terminal.push(format!("{}^C", label));
+                    session.prompt = None;
+                } else if input.just_pressed(KeyCode::Enter) {
+                    terminal.push(label);
+                    match session.submit_password(&users) {
+                        Ok(user) => terminal.push(format!("logged in as {}", user)),
+                        Err(err) => terminal.push(render::paint(TermColor::Red, format!("! {}", err))),
+                    }
+                }
+                return;
+            }
+
+            if ctrl && input.just_pressed(KeyCode::KeyC) {
+                terminal.push(format!("$ {}^C", history::mask_passwords(&current_command.take())));
+                if history.search.is_some() {
+                    history.search = None;
+                }
+                return;
+            }
+
+            if history.search.is_some() {
+                // ctrl+r search: typing edits the query, ctrl+r again goes further back,
+                // escape cancels and any other editing key puts the match on the prompt
+                let mut query_changed = !typed.is_empty();
+                if let Some(search) = &mut history.search {
+                    search.query.extend(typed);
+                    if keys.contains(&KeyCode::Backspace) {
+                        query_changed |= search.query.pop().is_some();
+                    }
+                }
+                if query_changed { history.search_step(false); }
+
+                if ctrl && input.just_pressed(KeyCode::KeyR) {
+                    history.search_step(true);
+                }
+
+                if input.just_pressed(KeyCode::Escape) {
+                    history.search = None;
+                } else if input.just_pressed(KeyCode::Enter) || keys.iter().any(|key| *key != KeyCode::Backspace) {
+                    if let Some(found) = history.finish_search() {
+                        current_command.set(found);
+                    }
+                }
+            } else {
+                for c in typed {
+                    current_command.insert(c);
+                }
+
+                for key in keys {
+                    match key {
+                        KeyCode::Backspace => { current_command.backspace(); }
+                        KeyCode::Delete => { current_command.delete(); }
+                        KeyCode::ArrowLeft if ctrl => { current_command.word_left(); }
+                        KeyCode::ArrowRight if ctrl => { current_command.word_right(); }
+                        KeyCode::ArrowLeft => { current_command.move_left(); }
+                        KeyCode::ArrowRight => { current_command.move_right(); }
+                        KeyCode::ArrowUp => {
+                            if let Some(line) = history.previous(&current_command.text) { current_command.set(line); }
+                        }
+                        KeyCode::ArrowDown => {
+                            if let Some(line) = history.next() { current_command.set(line); }
                         }
+                        KeyCode::Home => { current_command.home(); }
+                        KeyCode::End => { current_command.end(); }
+                        _ => {}
                     }
-                } else {
-                    for key in input.get_just_pressed() {
-
-                        match key {
-                            KeyCode::KeyA => { current_command.text.push_str("a") }
-                            KeyCode::KeyB => { current_command.text.push_str("b") }
-                            KeyCode::KeyC => { current_command.text.push_str("c") }
-                            KeyCode::KeyD => { current_command.text.push_str("d") }
-                            KeyCode::KeyE => { current_command.text.push_str("e") }
-                            KeyCode::KeyF => { current_command.text.push_str("f") }
-                            KeyCode::KeyG => { current_command.text.push_str("g") }
-                            KeyCode::KeyH => { current_command.text.push_str("h") }
-                            KeyCode::KeyI => { current_command.text.push_str("i") }
-                            KeyCode::KeyJ => { current_command.text.push_str("j") }
-                            KeyCode::KeyK => { current_command.text.push_str("k") }
-                            KeyCode::KeyL => { current_command.text.push_str("l") }
-                            KeyCode::KeyM => { current_command.text.push_str("m") }
-                            KeyCode::KeyN => { current_command.text.push_str("n") }
-                            KeyCode::KeyO => { current_command.text.push_str("o") }
-                            KeyCode::KeyP => { current_command.text.push_str("p") }
-                            KeyCode::KeyQ => { current_command.text.push_str("q") }
-                            KeyCode::KeyR => { current_command.text.push_str("r") }
-                            KeyCode::KeyS => { current_command.text.push_str("s") }
-                            KeyCode::KeyT => { current_command.text.push_str("t") }
-                            KeyCode::KeyU => { current_command.text.push_str("u") }
-                            KeyCode::KeyV => { current_command.text.push_str("v") }
-                            KeyCode::KeyW => { current_command.text.push_str("w") }
-                            KeyCode::KeyX => { current_command.text.push_str("x") }
-                            KeyCode::KeyY => { current_command.text.push_str("y") }
-                            KeyCode::KeyZ => { current_command.text.push_str("z") }
-                            KeyCode::Digit0 => { current_command.text.push_str("0") }
-                            KeyCode::Digit1 => { current_command.text.push_str("1") }
-                            KeyCode::Digit2 => { current_command.text.push_str("2") }
-                            KeyCode::Digit3 => { current_command.text.push_str("3") }
-                            KeyCode::Digit4 => { current_command.text.push_str("4") }
-                            KeyCode::Digit5 => { current_command.text.push_str("5") }
-                            KeyCode::Digit6 => { current_command.text.push_str("6") }
-                            KeyCode::Digit7 => { current_command.text.push_str("7") }
-                            KeyCode::Digit8 => { current_command.text.push_str("8") }
-                            KeyCode::Digit9 => { current_command.text.push_str("9") }
-                            KeyCode::Slash => { current_command.text.push_str("/") }
-                            KeyCode::Comma => { current_command.text.push_str(",") }
-                            KeyCode::Period => { current_command.text.push_str(".") }
-                            KeyCode::Semicolon => { current_command.text.push_str(";") }
-                            KeyCode::Quote => { current_command.text.push_str("'") }
-                            KeyCode::BracketLeft => { current_command.text.push_str("[") }
-                            KeyCode::BracketRight => { current_command.text.push_str("]") }
-                            KeyCode::Backslash => { current_command.text.push_str("\\") }
-                            KeyCode::Minus => { current_command.text.push_str("-") }
-                            KeyCode::Equal => { current_command.text.push_str("=") }
-                            KeyCode::Space => { current_command.text.push_str(" ") }
-                            KeyCode::Backspace => { current_command.text.pop(); }
-                            KeyCode::Enter => { 
-            
-                                let command = current_command.text.clone();
-                                terminal.text.push("$ ".to_owned() + &current_command.text.clone());
-                                current_command.text = String::from("");
-        
-                                if command.to_lowercase().eq("clear") { // >> CLEAR
-                                    println!("CLEAR COMMAND");
-                                    terminal.text = Vec::new();
-                                }
-                                else if command.to_lowercase().eq("exit") { // >> EXIT
-                                    next_console_state.set(ConsoleState::IsNotUsingConsole);
-                                }
-                                if command.to_lowercase().eq("hello") { // >> HELLO
-                                    println!("HELLO COMMAND");
-                                    terminal.text.push(make_hello());
-                                }
-                                if command.to_lowercase().eq("ls") { // >> LIST
-                                    println!("list directory COMMAND");
-                                    let cd = current_directory.0.clone();
-                                    terminal.text.push(cd.name.clone());
-                                    let list_values = terminal_list(cd);
-                                    let list = list_values.0;
-                                    if list.len() > 0 && list_values.1.len() > 0 {
-                                        for (i, line) in list.iter().enumerate() {
-                                            let length = format!("[{}]", list_values.1[i]);
-                                            terminal.text.push(length + &line);
-                                        }
-                                    } else {
-                                        terminal.text.push(String::from("~ empty"));
-                                    }
-
-                                }
-                                if command.to_lowercase().starts_with("cd ") { // >> CURRENT DIRECTORY
-                                    println!("move COMMAND");
-
-                                    let new_dir = command.split(" ").last().unwrap();
-                                    println!("{new_dir}");
-                                    if new_dir == ".." {
-                                        let cd = current_directory.0.clone();
-                                        println!("{}", cd.name);
-                                        let mut parent: Vec<&str> = cd.name.split("/").collect();
-                                        if parent.len() <= 2 {
-                                            println!("! current directory is root");
-                                        } else {
-                                            _=parent.pop();
-                                            _=parent.pop();
-                                            let mut new_dir = String::from("");
-                                            for item in parent {
-                                                new_dir = format!("{}{}/", new_dir, item);
-                                            }
-                                            if new_dir.eq("root/") {
-                                                current_directory.0 = root.root.clone();
-                                                terminal.text.push(String::from("root/"));
-                                            } else {
-                                                println!("moveing to: {}", new_dir);
-                                                match root.root.find_child_dir(&new_dir) {
-                                                    Some(dir) => {
-                                                        let name = dir.name.clone();
-                                                        current_directory.0 = dir.to_owned();
-                                                        terminal.text.push(name);
-                                                    },
-                                                    None => {
-                                                        terminal.text.push(String::from("! error moving to new directory"))
-                                                    },
-                                                }
-                                            }
-                                        }
-
-                                    }
-                                    else {
-                                        match current_directory.0.cd(new_dir) {
-                                            Ok(cd) => {
-                                                let name = cd.name.clone();
-                                                current_directory.0 = cd.to_owned();
-                                                terminal.text.push(name);
-                                            },
-                                            Err(_) => {
-                                                terminal.text.push(String::from("! could not find directory"))
-                                            },
-                                        };
-                                    }
-                                }
-
-                                if command.to_lowercase().eq("start-server") {
-
-                                }
+                }
 
+                if ctrl && input.just_pressed(KeyCode::KeyR) {
+                    history.start_search();
+                }
+
+                if input.just_pressed(KeyCode::Tab) {
+                    let completion = completion::complete(current_command.before_cursor(), &registry, &root.fs, &env, current_directory.0, session.user());
+                    for c in completion.insert.chars() {
+                        current_command.insert(c);
+                    }
+                    if !completion.candidates.is_empty() {
+                        terminal.push("$ ".to_owned() + &history::mask_passwords(&current_command.text));
+                        terminal.extend(completion::format_candidates(&completion.candidates));
+                    }
+                }
+            }
+
+            if input.just_pressed(KeyCode::Enter) && history.search.is_none() {
+                let command = current_command.take();
+                terminal.push("$ ".to_owned() + &history::mask_passwords(&command));
+                let (output, executed) = run_line(&command, LineContext {
+                    fs: &mut root.fs,
+                    current_directory: &mut current_directory,
+                    history: &mut history,
+                    session: &mut session,
+                    env: &mut env,
+                    processes: &mut processes,
+                    users: &users,
+                    registry: &registry,
+                    programs: &programs,
+                });
+                if let Some(console) = focused.0 {
+                    executed_evw.send_batch(executed.into_iter().map(|executed| CommandExecuted {
+                        console,
+                        name: executed.name,
+                        args: executed.args,
+                        status: executed.status,
+                    }));
+                }
+                terminal.extend(output.lines);
+                terminal.extend(output.errors);
+                for action in output.actions {
+                    match action {
+                        TerminalAction::Clear => { terminal.clear(); }
+                        TerminalAction::Exit => { next_console_state.set(ConsoleState::IsNotUsingConsole); }
+                        TerminalAction::Page(title, lines) => { open_pager.0 = Some(Pager::new(title, lines)); }
+                        TerminalAction::Edit(file) => {
+                            match FileEditor::open(&root.fs, file) {
+                                Ok(editor) => { open_editor.0 = Some(editor); }
+                                Err(err) => { terminal.push(render::paint(TermColor::Red, format!("! {}", err))); }
                             }
-                            _ => {}
                         }
                     }
                 }
@@ -676,9 +1044,3 @@ pub fn use_console(
         _ => {}
     }
 }
-
-
-
-
-
-
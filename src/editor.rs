@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+use crate::vfs::{NodeId, Vfs, VfsError};
+
+
+
+/// The file a console has open in `edit`, if any. While it is set the editor owns that console's screen
+#[derive(Component, Default)]
+pub struct OpenEditor(pub Option<FileEditor>);
+
+/// What the console should do after the editor has handled a frame of input
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum EditorEvent {
+    None,
+    Save,
+    Quit,
+}
+
+/// A small line editor over one VFS file
+pub struct FileEditor {
+    pub file: NodeId,
+    pub name: String,
+    pub lines: Vec<String>,
+    pub row: usize,
+    pub col: usize, // in chars
+    scroll: usize,  // first line on screen
+    pub dirty: bool,
+    confirm_quit: bool,
+    pub message: Option<String>,
+}
+
+impl FileEditor {
+    pub fn open(fs: &Vfs, file: NodeId) -> Result<Self, VfsError> {
+        let content = fs.read_file(file)?;
+        Ok(FileEditor {
+            file,
+            name: fs.path_of(file).to_string(),
+            lines: content.split('\n').map(String::from).collect(),
+            row: 0,
+            col: 0,
+            scroll: 0,
+            dirty: false,
+            confirm_quit: false,
+            message: None,
+        })
+    }
+
+    pub fn content(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Writes the buffer back into the file node
+    pub fn save(&mut self, fs: &mut Vfs) {
+        match fs.write_file(self.file, &self.content(), false) {
+            Ok(()) => {
+                self.dirty = false;
+                self.message = Some(format!("saved {} lines", self.lines.len()));
+            }
+            Err(err) => { self.message = Some(format!("! {}", err)); }
+        }
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    fn byte_index(&self, col: usize) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+    }
+
+    fn edited(&mut self) {
+        self.dirty = true;
+        self.confirm_quit = false;
+        self.message = None;
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.col);
+        self.lines[self.row].insert(i, c);
+        self.col += 1;
+        self.edited();
+    }
+
+    /// Splits the line at the cursor
+    pub fn newline(&mut self) {
+        let i = self.byte_index(self.col);
+        let rest = self.lines[self.row].split_off(i);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+        self.edited();
+    }
+
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let i = self.byte_index(self.col);
+            self.lines[self.row].remove(i);
+        } else if self.row > 0 {
+            // join onto the end of the previous line
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len();
+            self.lines[self.row].push_str(&line);
+        } else {
+            return;
+        }
+        self.edited();
+    }
+
+    pub fn delete(&mut self) {
+        if self.col < self.line_len() {
+            let i = self.byte_index(self.col);
+            self.lines[self.row].remove(i);
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&next);
+        } else {
+            return;
+        }
+        self.edited();
+    }
+
+    pub fn move_left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.col < self.line_len() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len());
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len());
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.col = self.line_len();
+    }
+
+    /// Handles one frame of input. `keys` are the editing keys that fired this frame, with repeat applied
+    pub fn handle_input(&mut self, typed: &[char], keys: &[KeyCode], input: &ButtonInput<KeyCode>) -> EditorEvent {
+        let ctrl = input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        if ctrl && input.just_pressed(KeyCode::KeyS) {
+            return EditorEvent::Save;
+        }
+        if ctrl && input.just_pressed(KeyCode::KeyQ) {
+            if self.dirty && !self.confirm_quit {
+                self.confirm_quit = true;
+                self.message = Some(String::from("unsaved changes, ^Q again to discard"));
+                return EditorEvent::None;
+            }
+            return EditorEvent::Quit;
+        }
+
+        for c in typed {
+            self.insert(*c);
+        }
+        if input.just_pressed(KeyCode::Enter) {
+            self.newline();
+        }
+
+        for key in keys {
+            match key {
+                KeyCode::Backspace => { self.backspace(); }
+                KeyCode::Delete => { self.delete(); }
+                KeyCode::ArrowLeft => { self.move_left(); }
+                KeyCode::ArrowRight => { self.move_right(); }
+                KeyCode::ArrowUp => { self.move_up(); }
+                KeyCode::ArrowDown => { self.move_down(); }
+                KeyCode::Home => { self.home(); }
+                KeyCode::End => { self.end(); }
+                _ => {}
+            }
+        }
+        EditorEvent::None
+    }
+
+    /// The screen: `rows - 1` numbered lines around the cursor and a status line at the bottom
+    pub fn render(&mut self, rows: usize) -> Vec<String> {
+        let visible = rows.saturating_sub(1).max(1);
+        if self.row < self.scroll {
+            self.scroll = self.row;
+        } else if self.row >= self.scroll + visible {
+            self.scroll = self.row + 1 - visible;
+        }
+
+        let mut screen = vec![];
+        for i in self.scroll..(self.scroll + visible) {
+            match self.lines.get(i) {
+                Some(line) if i == self.row => {
+                    let mut line = line.clone();
+                    line.insert(self.byte_index(self.col), '|');
+                    screen.push(format!("{:>3} {}", i + 1, line));
+                }
+                Some(line) => screen.push(format!("{:>3} {}", i + 1, line)),
+                None => screen.push(String::from("  ~")),
+            }
+        }
+
+        let status = match &self.message {
+            Some(message) => message.clone(),
+            None => format!("{}{} {}:{}  ^S save ^Q quit", self.name, if self.dirty { "*" } else { "" }, self.row + 1, self.col + 1),
+        };
+        screen.push(status);
+        screen
+    }
+}
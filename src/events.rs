@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+use crate::console::{self, Terminal};
+
+
+
+/// Prints lines on a console from any system, as if a command had printed them
+#[derive(Event, Clone, Debug)]
+pub struct TerminalOutput {
+    pub console: Entity,
+    pub lines: Vec<String>,
+}
+
+impl TerminalOutput {
+    pub fn new(console: Entity, lines: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        TerminalOutput { console, lines: lines.into_iter().map(Into::into).collect() }
+    }
+}
+
+/// Sent for every builtin or program that ran on a console, including the ones in pipelines and scripts,
+/// once the line it was part of has finished. Gameplay reads these to react to what the player runs
+#[derive(Event, Clone, Debug)]
+pub struct CommandExecuted {
+    pub console: Entity,
+    pub name: String, // the command's name, or the program's file name like `decend.exe`
+    pub args: Vec<String>,
+    pub status: i32, // 0 for success
+}
+
+/// Adds the lines sent with [`TerminalOutput`] to their console. Runs before the screens are redrawn
+/// so output sent during Update shows the same frame
+fn print_terminal_output(mut output_evr: EventReader<TerminalOutput>, mut terminal_query: Query<&mut Terminal>) {
+    for output in output_evr.read() {
+        match terminal_query.get_mut(output.console) {
+            Ok(mut terminal) => terminal.extend(output.lines.iter().cloned()),
+            Err(_) => warn!("terminal output sent to {:?}, which is not a console", output.console),
+        }
+    }
+}
+
+pub struct TerminalEventsPlugin;
+
+impl Plugin for TerminalEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TerminalOutput>()
+            .add_event::<CommandExecuted>()
+            .add_systems(PostUpdate, print_terminal_output.before(console::update_terminal));
+    }
+}
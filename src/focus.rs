@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use crate::camera::LookLocked;
+use crate::console::{self, FocusedConsole, TerminalGeometry, TerminalScreen};
+use crate::game::MainCamera;
+use crate::render::TerminalFont;
+
+
+
+// how long the camera takes to move to a screen or back, in seconds
+const TRANSITION_TIME: f32 = 0.6;
+
+// how far in front of the screen the camera stops, enough to see every column
+const FOCUS_DISTANCE: f32 = 1.2;
+
+struct Transition {
+    from: Transform,
+    to: Transform,
+    elapsed: f32,
+}
+
+/// Points the camera at a terminal's screen while the player uses it, and back at the player afterwards
+#[derive(Resource, Default)]
+pub struct CameraFocus {
+    transition: Option<Transition>,
+    return_to: Option<Transform>, // where the camera was before it moved, set while a terminal is in use
+}
+
+impl CameraFocus {
+    /// True when the camera belongs to the player, neither at a screen nor on its way
+    pub fn is_free(&self) -> bool {
+        self.transition.is_none() && self.return_to.is_none()
+    }
+}
+
+/// Starts moving the camera to face the focused console's screen
+pub fn focus_terminal(
+    mut focus: ResMut<CameraFocus>,
+    focused: Res<FocusedConsole>,
+    children_query: Query<&Children>,
+    screen_query: Query<&GlobalTransform, With<TerminalScreen>>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+    geometry: Res<TerminalGeometry>,
+    font: Res<TerminalFont>,
+    mut look: ResMut<LookLocked>,
+) {
+    let (console, camera) = match (focused.0, camera_query.get_single_mut()) {
+        (Some(console), Ok(camera)) => (console, camera),
+        _ => { return; }
+    };
+    let screen = children_query.get(console).ok()
+        .and_then(|children| children.iter().find_map(|child| screen_query.get(*child).ok()));
+    let screen = match screen {
+        Some(screen) => screen,
+        None => { return; }
+    };
+
+    // stand off the side of the screen the player is already on
+    let center = console::screen_center(&geometry, font.advance());
+    let target = screen.transform_point(center);
+    let eye = [Vec3::NEG_Z, Vec3::Z]
+        .map(|side| screen.transform_point(center + side * FOCUS_DISTANCE))
+        .into_iter()
+        .min_by(|a, b| a.distance(camera.translation).total_cmp(&b.distance(camera.translation)))
+        .unwrap_or(target);
+
+    // coming back in while still leaving keeps the original way back to the player
+    let return_to = focus.return_to.unwrap_or(*camera);
+    focus.return_to = Some(return_to);
+    focus.transition = Some(Transition {
+        from: *camera,
+        to: Transform::from_translation(eye).looking_at(target, Vec3::Y),
+        elapsed: 0.0,
+    });
+    look.0 = true;
+}
+
+/// Starts moving the camera back to where the player left it
+pub fn release_terminal(mut focus: ResMut<CameraFocus>, camera_query: Query<&Transform, With<MainCamera>>) {
+    let (return_to, camera) = match (focus.return_to.take(), camera_query.get_single()) {
+        (Some(return_to), Ok(camera)) => (return_to, camera),
+        _ => { return; }
+    };
+    focus.transition = Some(Transition { from: *camera, to: return_to, elapsed: 0.0 });
+}
+
+pub fn animate_camera_focus(
+    time: Res<Time>,
+    mut focus: ResMut<CameraFocus>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+    mut look: ResMut<LookLocked>,
+) {
+    let transition = match focus.transition.as_mut() {
+        Some(transition) => transition,
+        None => { return; }
+    };
+    transition.elapsed += time.delta_seconds();
+    let t = (transition.elapsed / TRANSITION_TIME).min(1.0);
+    let eased = t * t * (3.0 - 2.0 * t);
+
+    if let Ok(mut camera) = camera_query.get_single_mut() {
+        camera.translation = transition.from.translation.lerp(transition.to.translation, eased);
+        camera.rotation = transition.from.rotation.slerp(transition.to.rotation, eased);
+    }
+
+    if t >= 1.0 {
+        focus.transition = None;
+        // back with the player, who can look around again
+        if focus.return_to.is_none() {
+            look.0 = false;
+        }
+    }
+}
+
+/// Forgets any focus when the game is left, the camera goes with it
+pub fn reset_camera_focus(mut focus: ResMut<CameraFocus>, mut look: ResMut<LookLocked>) {
+    *focus = CameraFocus::default();
+    look.0 = false;
+}
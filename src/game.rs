@@ -1,4 +1,4 @@
-use std::{default, f32::consts::PI, iter::once, fs::read_to_string};
+use std::{default, f32::consts::PI, iter::once};
 // use bevy_flycam::prelude::*;
 use bevy::{
     ecs::{entity, system::{Command, RunSystemOnce, SystemId}}, tasks::IoTaskPool, math::vec3, prelude::*, render::camera::Viewport, text, transform::{self, TransformSystem}, winit::WinitSettings
@@ -10,6 +10,10 @@ use bevy::window::{CursorGrabMode, PrimaryWindow};
 use crate::{camera::*, postprocessing};
 use crate::map;
 use crate::console;
+use crate::events::{CommandExecuted, TerminalOutput};
+use crate::focus;
+use crate::persist;
+use crate::vfs_asset::{self, VfsDefinition, VfsDefinitionHandle};
 use bevy_rapier3d::{parry::query::Ray, prelude::*};
 
 use serde::{Deserialize, Serialize};
@@ -87,12 +91,13 @@ pub fn game_setup(
     // mut windowsettings: Query<ResMut<WinitSettings>>,
     mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
     asset_server: Res<AssetServer>,
+    geometry: Res<console::TerminalGeometry>,
+    definitions: Res<Assets<VfsDefinition>>,
+    definition: Res<VfsDefinitionHandle>,
 ) {
 
-    let root = make_defualt_directory();
-    let dir = root.root.clone();
+    let root = make_defualt_directory(&definitions, &definition);
     commands.insert_resource(root);
-    commands.insert_resource(console::CurrentDirectory(dir));
 
 
     let mut window_size: (f32, f32) = (0.0, 0.0);
@@ -256,7 +261,7 @@ pub fn game_setup(
 
     transform.rotate_y(0.17);
 
-    console::spawn_console(transform, String::from("primary"), &asset_server, &mut commands, &mut meshes, &mut materials);
+    console::spawn_console(transform, String::from("primary"), &asset_server, &mut commands, &geometry);
 
 
     let transform2 = Transform {
@@ -294,8 +299,13 @@ pub fn setup_physics(mut commands: Commands) {
 
 pub fn update_player_camera(
     mut player_camera: Query<(&mut Transform), With<MainCamera>>,
-    mut player_body: Query<&Transform, (With<PlayerBody>, Without<MainCamera>)>
+    mut player_body: Query<&Transform, (With<PlayerBody>, Without<MainCamera>)>,
+    focus: Res<focus::CameraFocus>,
 ) {
+    // the camera is at a terminal, or on its way there or back
+    if !focus.is_free() {
+        return;
+    }
 
     if let Ok(mut body_transform) = player_body.get_single_mut() {
         if let Ok(mut camera_transform) = player_camera.get_single_mut() {
@@ -318,6 +328,23 @@ pub fn spawn_new_map(
     map::spawn_cubes_from_matrix(&mut commands, &mut meshes, &mut materials, &mut new_rooms, (0.0, 0.0, 0.0));
 }
 
+/// Reacts to what the player runs on the consoles. decend.exe takes them down to a new floor, leaving the old one behind
+pub fn react_to_commands(
+    mut executed_evr: EventReader<CommandExecuted>,
+    mut output_evw: EventWriter<TerminalOutput>,
+    mut commands: Commands,
+) {
+    for executed in executed_evr.read() {
+        if executed.name == "decend.exe" && executed.status == 0 {
+            commands.add(|world: &mut World| {
+                world.run_system_once(map::despawn_all);
+                world.run_system_once(spawn_new_map);
+            });
+            output_evw.send(TerminalOutput::new(executed.console, ["a new floor has been generated"]));
+        }
+    }
+}
+
 
 
 pub fn update_settings(mut settings: Query<&mut postprocessing::PostProcessSettings>, time: Res<Time>) {
@@ -336,44 +363,18 @@ pub fn update_settings(mut settings: Query<&mut postprocessing::PostProcessSetti
 
 //                                           MAKE DEFAULT DIRECTORY
 
-pub fn make_defualt_directory () -> console::GameDirectory {
-    
-    let mut root = console::GameDirectory {
-        root: console::Directory::new(String::from("root/"))
-    };
-
-    // _=root.root.add_dir(String::from("root/users/")).unwrap();
-    // _=root.root.add_dir(String::from("root/system/")).unwrap();
-    // _=root.root.add_dir(String::from("root/programs/")).unwrap();
-    // _=root.root.add_dir(String::from("root/files/")).unwrap();
-
-    // let users = root.root.get_dir("root/users/").unwrap();
-    // _=users.add_dir(String::from("root/users/steve/")).unwrap();
-    // _=users.add_dir(String::from("root/users/sumi/")).unwrap();
-
-    // let programs = root.root.get_dir("root/programs/").unwrap();
-    // _=programs.add_program(String::from("root/programs/save.exe")).unwrap();
-    // _=programs.add_program(String::from("root/programs/decend.exe")).unwrap();
-
-    // let files = root.root.get_dir("root/files/").unwrap();
-    // _=files.add_dir(String::from("root/files/floors/")).unwrap();
-    // _=files.add_dir(String::from("root/files/enemies/")).unwrap();
-    // _=files.add_dir(String::from("root/files/items/")).unwrap();
-
-    // let floors = files.get_dir("root/files/floors/").unwrap();
-    // _=floors.add_file(String::from("root/files/floors/1.txt")).unwrap();
-    // _=floors.add_file(String::from("root/files/floors/2.txt")).unwrap();
-    // _=floors.add_file(String::from("root/files/floors/3.txt")).unwrap();
-
-    let init_file = read_to_string("./src/default_terminal_state.json").expect("could not read json");
-    let value: console::Directory = serde_json::from_str(&init_file).expect("could not parse json");
-    
-    for (name, children) in value.children {
-        println!("{}", name);
+/// Restores the saved terminal filesystem, bringing in whatever changed in the definition since it was saved.
+/// Without a save it is built fresh from the definition asset, or the built in one while the asset is still loading
+pub fn make_defualt_directory (definitions: &Assets<VfsDefinition>, handle: &VfsDefinitionHandle) -> console::GameDirectory {
+    let definition = vfs_asset::current_definition(definitions, handle);
+    match persist::load() {
+        Some(saved) => {
+            let mut root = console::GameDirectory::restore(&saved.tree, saved.definition);
+            root.merge_definition(definition);
+            root
+        }
+        None => console::GameDirectory::new(definition),
     }
-
-
-    return root;
 }
 
 
@@ -401,6 +402,8 @@ pub fn check_for_interactions(
     input: Res<ButtonInput<KeyCode>>,
     mut console_state: Res<State<console::ConsoleState>>,
     mut next_console_state: ResMut<NextState<console::ConsoleState>>,
+    parent_query: Query<&Parent>,
+    mut focused: ResMut<console::FocusedConsole>,
 ) {
     let mut is_interactable = false;
     let mut is_interacting = false;
@@ -419,6 +422,10 @@ pub fn check_for_interactions(
                                 console::ConsoleState::IsNotUsingConsole => {
                                     interaction_ui.sections[0].value = String::from("[F] - use terminal");
                                     if input.pressed(KeyCode::KeyF) || input.just_pressed(KeyCode::KeyF) {
+                                        // the collider hangs off the console entity, which holds the console's state
+                                        if let (InteractionType::Console, Ok(console)) = (interaction_type, parent_query.get(object)) {
+                                            focused.0 = Some(console.get());
+                                        }
                                         next_console_state.set(console::ConsoleState::IsUsingConsole);
                                     }
                                 }
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::PathBuf;
+use bevy::prelude::*;
+use crate::userdata;
+
+
+
+const MAX_HISTORY: usize = 500;
+
+/// Commands entered on one console. Saved to the user data directory under the console id
+/// when it was loaded from there, a default history is only kept in memory
+#[derive(Component, Default)]
+pub struct CommandHistory {
+    pub id: String,
+    persistent: bool,
+    pub entries: Vec<String>,
+    position: Option<usize>, // index of the entry being shown while browsing with up/down
+    draft: String,           // what was typed before browsing started
+    pub search: Option<HistorySearch>,
+}
+
+/// State of a ctrl+r reverse search
+#[derive(Default)]
+pub struct HistorySearch {
+    pub query: String,
+    pub index: Option<usize>, // entry currently matching the query
+}
+
+/// A command line with the passwords given to `login` and `su` starred out, for keeping it in the history or scrollback.
+/// Lines without one are returned as they are
+pub fn mask_passwords(line: &str) -> String {
+    let mut words: Vec<String> = line.split_whitespace().map(String::from).collect();
+    let mut masked = false;
+    let mut password_at = None; // where the password of a login or su would be
+    let mut at_command = true;
+    for (i, word) in words.iter_mut().enumerate() {
+        if at_command && (word == "login" || word == "su") {
+            password_at = Some(i + 2);
+        } else if password_at == Some(i) {
+            // keep a `;` or `|` ending the word, it belongs to the line rather than the password
+            let password_len = word.trim_end_matches([';', '|', '&']).len();
+            word.replace_range(..password_len, "****");
+            masked = true;
+        }
+        at_command = word.ends_with([';', '|', '&']);
+        if at_command {
+            password_at = None;
+        }
+    }
+
+    if masked { words.join(" ") } else { String::from(line) }
+}
+
+impl CommandHistory {
+    fn path(id: &str) -> PathBuf {
+        userdata::data_dir().join("history").join(format!("{}.txt", userdata::file_name(id)))
+    }
+
+    /// Reads the saved history for a console, starting empty if there is none
+    pub fn load(id: &str) -> Self {
+        let entries = match fs::read_to_string(CommandHistory::path(id)) {
+            Ok(text) => text.lines().map(String::from).collect(),
+            Err(_) => vec![],
+        };
+
+        CommandHistory { id: String::from(id), entries, persistent: true, ..default() }
+    }
+
+    pub fn save(&self) {
+        if !self.persistent {
+            return;
+        }
+        let path = CommandHistory::path(&self.id);
+        let result = path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, self.entries.join("\n")));
+
+        if let Err(err) = result {
+            warn!("could not save terminal history to {}: {}", path.display(), err);
+        }
+    }
+
+    /// Adds an entered command, without any password in it, and stops browsing.
+    /// Empty lines and repeats of the last entry are skipped
+    pub fn record(&mut self, command: &str) {
+        self.position = None;
+        self.draft.clear();
+        self.search = None;
+
+        let command = mask_passwords(command);
+        let command = command.trim();
+        if command.is_empty() || self.entries.last().map_or(false, |last| last == command) {
+            return;
+        }
+
+        self.entries.push(String::from(command));
+        if self.entries.len() > MAX_HISTORY {
+            let overflow = self.entries.len() - MAX_HISTORY;
+            self.entries.drain(..overflow);
+        }
+        self.save();
+    }
+
+    /// Steps back to an older entry. `current` is the line being edited, kept so down can restore it
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let position = match self.position {
+            Some(0) => 0,
+            Some(position) => position - 1,
+            None => {
+                if self.entries.is_empty() { return None; }
+                self.draft = String::from(current);
+                self.entries.len() - 1
+            }
+        };
+
+        self.position = Some(position);
+        Some(self.entries[position].clone())
+    }
+
+    /// Steps forward to a newer entry, ending on the line that was being typed
+    pub fn next(&mut self) -> Option<String> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(self.entries[position + 1].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    pub fn start_search(&mut self) {
+        self.search = Some(HistorySearch::default());
+    }
+
+    /// Finds the newest entry containing the query, older than the current match when `older` is set
+    pub fn search_step(&mut self, older: bool) {
+        let entries = &self.entries;
+        if let Some(search) = &mut self.search {
+            let end = match (older, search.index) {
+                (true, Some(index)) => index,
+                _ => entries.len(),
+            };
+            if let Some(index) = entries[..end].iter().rposition(|entry| entry.contains(&search.query)) {
+                search.index = Some(index);
+            } else if !older {
+                search.index = None;
+            }
+        }
+    }
+
+    /// Leaves search mode, returning the matched entry if there was one
+    pub fn finish_search(&mut self) -> Option<String> {
+        let search = self.search.take()?;
+        search.index.map(|index| self.entries[index].clone())
+    }
+
+    /// The prompt shown while searching
+    pub fn search_prompt(&self) -> Option<String> {
+        let search = self.search.as_ref()?;
+        let found = search.index.map_or("", |index| self.entries[index].as_str());
+        Some(format!("(reverse-i-search)'{}': {}", search.query, found))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_are_masked() {
+        assert_eq!(mask_passwords("login kai moonlight"), "login kai ****");
+        assert_eq!(mask_passwords("  su root ashfall  "), "su root ****");
+        assert_eq!(mask_passwords("ls; su kai moonlight; whoami"), "ls; su kai ****; whoami");
+        assert_eq!(mask_passwords("su kai moonlight&& ls"), "su kai ****&& ls");
+        assert_eq!(mask_passwords("echo login kai moonlight"), "echo login kai moonlight");
+        assert_eq!(mask_passwords("su kai"), "su kai");
+        assert_eq!(mask_passwords("echo 'a  b'"), "echo 'a  b'");
+    }
+
+    #[test]
+    fn recorded_lines_have_no_passwords() {
+        let mut history = CommandHistory::default();
+        history.record("login kai moonlight");
+        history.record("su");
+        assert_eq!(history.entries, ["login kai ****", "su"]);
+    }
+}
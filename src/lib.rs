@@ -0,0 +1,26 @@
+//! The game and its terminal. The window lives in main.rs, the headless shell in src/bin/oxyb-shell.rs
+
+pub mod camera;
+pub mod mainmenu;
+pub mod game;
+pub mod map;
+pub mod pager;
+pub mod persist;
+pub mod postprocessing;
+pub mod models;
+pub mod console;
+pub mod commands;
+pub mod completion;
+pub mod editor;
+pub mod events;
+pub mod focus;
+pub mod history;
+pub mod process;
+pub mod programs;
+pub mod render;
+pub mod shell;
+pub mod userdata;
+pub mod users;
+pub mod vfs;
+pub mod vfs_asset;
+pub mod floor;
@@ -12,14 +12,7 @@ use camera::prelude::game::{check_for_interactions, MainCamera};
 use map::{Room, Rotation};
 use iyes_perf_ui::prelude::*;
 
-mod camera;
-mod mainmenu;
-mod game;
-mod map;
-mod postprocessing;
-mod models;
-mod console;
-mod floor;
+use oxyb::{camera, mainmenu, game, map, persist, postprocessing, console, commands, events, focus, process, programs, render, users, vfs_asset};
 
 
 
@@ -71,9 +64,17 @@ fn main() {
             RapierPhysicsPlugin::<NoUserData>::default(),
             RapierDebugRenderPlugin::default(),
             // console::ConsolePlugin,
+            commands::CommandsPlugin,
+            programs::ProgramsPlugin,
+            vfs_asset::VfsAssetPlugin,
+            events::TerminalEventsPlugin,
         ))
-        .insert_resource(console::Terminal { text: vec![String::from("universal instruction terminal v0.2.3")], upper: 13, lower: 1 })
-        .insert_resource(console::CurrentCommand { text: String::from("") })
+        .init_resource::<console::TerminalGeometry>()
+        .init_resource::<console::FocusedConsole>()
+        .init_resource::<render::TerminalFont>()
+        .init_resource::<render::TerminalPalette>()
+        .init_resource::<users::Users>()
+        .init_resource::<focus::CameraFocus>()
         .insert_resource(game::ActiveCamera::Primary)
         .init_state::<mainmenu::GameState>()
         .init_state::<console::ConsoleState>()
@@ -87,17 +88,23 @@ fn main() {
         ).run_if(in_state(mainmenu::GameState::MainMenu)))
         // GAME SYSTEMS
         .add_systems(OnEnter(mainmenu::GameState::Game), (game::game_setup, game::setup_physics))
-        .add_systems(OnExit(mainmenu::GameState::Game), (game::despawn_all, map::despawn_all))
+        .add_systems(OnExit(mainmenu::GameState::Game), (game::despawn_all, map::despawn_all, persist::save_on_exit, focus::reset_camera_focus))
+        .add_systems(OnEnter(console::ConsoleState::IsUsingConsole), focus::focus_terminal)
+        .add_systems(OnEnter(console::ConsoleState::IsNotUsingConsole), focus::release_terminal)
         .add_systems(Update, (
             map::rotate_map,
             game::update_settings,
             keyboard_input,
             game::update_player_camera,
+            focus::animate_camera_focus.after(game::update_player_camera),
             console::use_console,
+            process::tick_processes.after(console::use_console),
+            game::react_to_commands.after(console::use_console),
             check_for_interactions,
             // game::switch_cameras,
         ).run_if(in_state(mainmenu::GameState::Game)))
-        .add_systems(PostUpdate, (console::update_terminal).run_if(in_state(mainmenu::GameState::Game)))
+        .add_systems(PostUpdate, (console::update_terminal, render::blink_text).run_if(in_state(mainmenu::GameState::Game)))
+        .add_systems(Last, persist::save_filesystem)
         .run();
 }
 
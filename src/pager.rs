@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use crate::render::{self, bold, Row};
+
+
+
+/// The text a console is paging through, if any. While it is set the pager owns that console's screen
+#[derive(Component, Default)]
+pub struct OpenPager(pub Option<Pager>);
+
+/// A read only view of text that is too long for the screen, like `less`
+pub struct Pager {
+    title: String,
+    lines: Vec<String>,
+    top: usize, // first wrapped row on screen
+}
+
+impl Pager {
+    pub fn new(title: String, lines: Vec<String>) -> Self {
+        Pager { title, lines, top: 0 }
+    }
+
+    fn rows(&self, columns: usize) -> Vec<Row> {
+        self.lines.iter().flat_map(|line| render::wrap(line, columns)).collect()
+    }
+
+    /// Handles one frame of input. `keys` are the editing keys that fired this frame, with repeat applied.
+    /// Returns false once the player has quit
+    pub fn handle_input(&mut self, typed: &[char], keys: &[KeyCode], input: &ButtonInput<KeyCode>, rows: usize, columns: usize) -> bool {
+        let page = rows.saturating_sub(1).max(1) as isize;
+        let last = self.rows(columns).len().saturating_sub(page as usize) as isize;
+        let mut top = self.top as isize;
+
+        for c in typed {
+            match c {
+                'q' => { return false; }
+                ' ' | 'f' => { top += page; }
+                'b' => { top -= page; }
+                'j' => { top += 1; }
+                'k' => { top -= 1; }
+                'g' => { top = 0; }
+                'G' => { top = last; }
+                _ => {}
+            }
+        }
+        for key in keys {
+            match key {
+                KeyCode::ArrowDown => { top += 1; }
+                KeyCode::ArrowUp => { top -= 1; }
+                _ => {}
+            }
+        }
+        if input.just_pressed(KeyCode::Escape) {
+            return false;
+        }
+        if input.just_pressed(KeyCode::Enter) { top += 1; }
+        if input.just_pressed(KeyCode::PageDown) { top += page; }
+        if input.just_pressed(KeyCode::PageUp) { top -= page; }
+        if input.just_pressed(KeyCode::Home) { top = 0; }
+        if input.just_pressed(KeyCode::End) { top = last; }
+
+        self.top = top.clamp(0, last) as usize;
+        true
+    }
+
+    /// The screen: a page of text and a status line at the bottom
+    pub fn render(&self, rows: usize, columns: usize) -> Vec<Row> {
+        let page = rows.saturating_sub(1).max(1);
+        let all = self.rows(columns);
+        let end = (self.top + page).min(all.len());
+
+        let mut screen: Vec<Row> = all[self.top.min(end)..end].to_vec();
+        screen.resize(page, vec![]);
+        let status = format!("{} {}-{}/{}  space page q quit", self.title, self.top + 1, end, all.len());
+        screen.extend(render::wrap(&bold(status), columns).into_iter().take(1));
+        screen
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::PathBuf;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::console::{Directory, GameDirectory};
+use crate::userdata;
+
+
+
+/// Bump this when the save format changes, and teach [`migrate`] to read the old one
+const SAVE_VERSION: u32 = 1;
+
+// changes are written at most this often, in seconds, so a busy process does not write every frame
+const SAVE_INTERVAL: f32 = 2.0;
+
+/// The terminal filesystem as it is written to the save directory
+#[derive(Serialize, Deserialize)]
+pub struct SavedFilesystem {
+    pub version: u32,
+    pub tree: Directory,       // everything on the filesystem, the player's files included
+    pub definition: Directory, // the definition `tree` was built from, so newer definitions can still be merged in
+}
+
+fn path() -> PathBuf {
+    userdata::data_dir().join("saves").join("filesystem.json")
+}
+
+/// Brings a save written by any earlier version up to the current format
+fn migrate(value: Value) -> Result<SavedFilesystem, String> {
+    let version = value.get("version").and_then(Value::as_u64).ok_or("the save has no version")?;
+    match version as u32 {
+        SAVE_VERSION => serde_json::from_value(value).map_err(|err| err.to_string()),
+        version if version > SAVE_VERSION => Err(format!("the save is from a newer version ({})", version)),
+        version => Err(format!("no migration from save version {}", version)),
+    }
+}
+
+/// Reads the saved filesystem. No save is not an error, a broken one is reported and ignored
+pub fn load() -> Option<SavedFilesystem> {
+    let path = path();
+    let text = fs::read_to_string(&path).ok()?;
+    let loaded = serde_json::from_str(&text).map_err(|err| err.to_string()).and_then(migrate);
+    match loaded {
+        Ok(saved) => Some(saved),
+        Err(err) => {
+            warn!("could not load the filesystem from {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+pub fn save(root: &GameDirectory) {
+    let saved = SavedFilesystem { version: SAVE_VERSION, tree: root.fs.to_directory(), definition: root.definition().clone() };
+    let path = path();
+    let result = serde_json::to_string_pretty(&saved)
+        .map_err(std::io::Error::from)
+        .and_then(|json| path.parent().map_or(Ok(()), fs::create_dir_all).map(|_| json))
+        .and_then(|json| fs::write(&path, json));
+
+    if let Err(err) = result {
+        warn!("could not save the filesystem to {}: {}", path.display(), err);
+    }
+}
+
+/// Writes the filesystem a little after it changes, and straight away when the game closes
+pub fn save_filesystem(
+    root: Option<Res<GameDirectory>>,
+    time: Res<Time>,
+    mut exit_evr: EventReader<AppExit>,
+    mut pending: Local<Option<f32>>, // seconds since the first unsaved change
+) {
+    let root = match root {
+        Some(root) => root,
+        None => { return; }
+    };
+
+    if root.is_changed() && !root.is_added() && pending.is_none() {
+        *pending = Some(0.0);
+    }
+    let exiting = exit_evr.read().count() > 0;
+    if let Some(waited) = pending.as_mut() {
+        *waited += time.delta_seconds();
+        if *waited >= SAVE_INTERVAL || exiting {
+            save(&root);
+            *pending = None;
+        }
+    }
+}
+
+/// Saves when leaving the game for the menu, whatever is still pending
+pub fn save_on_exit(root: Option<Res<GameDirectory>>) {
+    if let Some(root) = root {
+        save(&root);
+    }
+}
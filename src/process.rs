@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use crate::console::{GameDirectory, Terminal};
+use crate::shell::ShellEnv;
+use crate::render::{paint, TermColor};
+use crate::vfs::Vfs;
+
+
+
+// exit status of a process stopped with ctrl+c or kill, as in bash
+pub const INTERRUPTED: i32 = 130;
+
+pub type Pid = u32;
+
+/// What a process reports after each tick
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ProcessState {
+    Running,
+    Exited(i32),
+}
+
+/// What a process gets to work with during one tick
+pub struct ProcessContext<'a> {
+    pub delta: f32, // seconds since the last tick
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    pub fs: &'a mut Vfs,
+}
+
+impl<'a> ProcessContext<'a> {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.stdout.push(line.into());
+    }
+
+    pub fn eprint(&mut self, line: impl Into<String>) {
+        self.stderr.push(paint(TermColor::Red, format!("! {}", line.into())));
+    }
+}
+
+/// A program that keeps running across frames. Spawn one from a program handler with
+/// [`crate::programs::ProgramContext::spawn`], it is then ticked every frame until it exits or is killed
+pub trait Process: Send + Sync + 'static {
+    fn tick(&mut self, ctx: &mut ProcessContext) -> ProcessState;
+}
+
+pub struct ProcessEntry {
+    pub pid: Pid,
+    pub command: String,
+    pub background: bool,
+    pub elapsed: f32,
+    process: Box<dyn Process>,
+}
+
+/// The processes running on a console. At most one is in the foreground,
+/// and while it runs the prompt is hidden and ctrl+c stops it
+#[derive(Component)]
+pub struct ProcessTable {
+    next_pid: Pid,
+    processes: Vec<ProcessEntry>,
+}
+
+impl Default for ProcessTable {
+    fn default() -> Self {
+        ProcessTable { next_pid: 1, processes: vec![] }
+    }
+}
+
+impl ProcessTable {
+    /// Starts a process in the foreground, sending the current foreground process to the background
+    pub fn spawn(&mut self, command: String, process: Box<dyn Process>) -> Pid {
+        for entry in self.processes.iter_mut() {
+            entry.background = true;
+        }
+
+        let pid = self.next_pid;
+        self.next_pid += 1;
+        self.processes.push(ProcessEntry { pid, command, background: false, elapsed: 0.0, process });
+        pid
+    }
+
+    /// The pid the next spawned process will get
+    pub fn next_pid(&self) -> Pid {
+        self.next_pid
+    }
+
+    pub fn get(&self, pid: Pid) -> Option<&ProcessEntry> {
+        self.processes.iter().find(|entry| entry.pid == pid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ProcessEntry> {
+        self.processes.iter()
+    }
+
+    pub fn foreground(&self) -> Option<Pid> {
+        self.processes.iter().find(|entry| !entry.background).map(|entry| entry.pid)
+    }
+
+    /// The most recently started background process, what `fg` picks without an argument
+    pub fn last_background(&self) -> Option<Pid> {
+        self.processes.iter().rev().find(|entry| entry.background).map(|entry| entry.pid)
+    }
+
+    pub fn set_background(&mut self, pid: Pid) {
+        if let Some(entry) = self.processes.iter_mut().find(|entry| entry.pid == pid) {
+            entry.background = true;
+        }
+    }
+
+    /// Brings a process to the foreground. Returns false if there is no such process
+    pub fn set_foreground(&mut self, pid: Pid) -> bool {
+        if self.get(pid).is_none() {
+            return false;
+        }
+        for entry in self.processes.iter_mut() {
+            entry.background = entry.pid != pid;
+        }
+        true
+    }
+
+    /// Stops a process without letting it finish its tick
+    pub fn kill(&mut self, pid: Pid) -> Option<ProcessEntry> {
+        let index = self.processes.iter().position(|entry| entry.pid == pid)?;
+        Some(self.processes.remove(index))
+    }
+}
+
+
+
+//                                                    TICKING
+
+/// Ticks every process on every console once a frame.
+/// Their output goes straight to their console, wherever in a script they were started from
+pub fn tick_processes(
+    time: Res<Time>,
+    mut root: ResMut<GameDirectory>,
+    mut console_query: Query<(&mut ProcessTable, &mut Terminal, &mut ShellEnv)>,
+) {
+    for (table, terminal, env) in console_query.iter_mut() {
+        if !table.processes.is_empty() {
+            tick_console(time.delta_seconds(), &mut root.fs, table, terminal, env);
+        }
+    }
+}
+
+fn tick_console(delta: f32, fs: &mut Vfs, mut table: Mut<ProcessTable>, mut terminal: Mut<Terminal>, mut env: Mut<ShellEnv>) {
+    let output = table.tick(delta, fs);
+
+    // only touch the terminal when there is output, so quiet frames do not redraw it
+    if !output.lines.is_empty() {
+        terminal.extend(output.lines);
+    }
+    if let Some(code) = output.status {
+        env.last_status = code;
+    }
+}
+
+/// What a console's processes did during one tick
+#[derive(Default)]
+pub struct TickOutput {
+    pub lines: Vec<String>,  // what they printed, then a line for each background process that finished
+    pub status: Option<i32>, // exit status of the foreground process, if it finished
+}
+
+impl ProcessTable {
+    /// Ticks every process once, removing the ones that exit
+    pub fn tick(&mut self, delta: f32, fs: &mut Vfs) -> TickOutput {
+        let mut output = TickOutput::default();
+        let mut finished = vec![];
+
+        for entry in self.processes.iter_mut() {
+            let mut ctx = ProcessContext { delta, stdout: vec![], stderr: vec![], fs: &mut *fs };
+            let state = entry.process.tick(&mut ctx);
+            entry.elapsed += delta;
+
+            output.lines.extend(ctx.stdout);
+            output.lines.extend(ctx.stderr);
+            if let ProcessState::Exited(code) = state {
+                finished.push((entry.pid, code));
+            }
+        }
+
+        for (pid, code) in finished {
+            let entry = match self.kill(pid) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if entry.background {
+                let status = if code == 0 { String::from("done") } else { format!("exit {}", code) };
+                output.lines.push(format!("[{}] {}  {}", entry.pid, status, entry.command));
+            } else {
+                output.status = Some(code);
+            }
+        }
+        output
+    }
+}
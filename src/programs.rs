@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crate::commands::{CommandContext, CommandOutput, ExecutedCommand, ExitStatus};
+use crate::process::{Process, ProcessContext as TickContext, ProcessState};
+use crate::render::{paint, TermColor};
+use crate::shell::ShellEnv;
+use crate::vfs::{Access, NodeId, NodeKind, Vfs, VfsError, VfsPath};
+
+
+
+// where bare program names are looked up when $PATH is not set
+const DEFAULT_PATH: &str = "/programs";
+
+/// What a program gets to work with while it runs
+pub struct ProgramContext<'a, 'b> {
+    pub argv: Vec<String>, // argv[0] is the program's path as typed
+    pub stdin: Vec<String>,
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    pub shell: &'a mut CommandContext<'b>, // current directory, filesystem and environment
+    spawned: Option<Box<dyn Process>>,
+}
+
+impl<'a, 'b> ProgramContext<'a, 'b> {
+    pub fn current_directory(&self) -> NodeId {
+        self.shell.current_directory.0
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.stdout.push(line.into());
+    }
+
+    pub fn eprint(&mut self, line: impl Into<String>) {
+        self.stderr.push(paint(TermColor::Red, format!("! {}", line.into())));
+    }
+
+    /// Keeps the program running after the handler returns. The process is ticked every frame
+    /// and its exit code replaces the handler's
+    pub fn spawn(&mut self, process: impl Process) {
+        self.spawned = Some(Box::new(process));
+    }
+}
+
+/// Rust code behind a `Program` node. Returns the exit code, 0 for success
+pub type ProgramHandler = Box<dyn Fn(&mut ProgramContext) -> i32 + Send + Sync>;
+
+/// Handlers for program nodes, keyed by the program's file name (`create_server.exe`)
+#[derive(Resource, Default)]
+pub struct ProgramRegistry {
+    handlers: HashMap<String, ProgramHandler>,
+}
+
+impl ProgramRegistry {
+    pub fn register(&mut self, name: &str, handler: impl Fn(&mut ProgramContext) -> i32 + Send + Sync + 'static) {
+        self.handlers.insert(String::from(name), Box::new(handler));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ProgramHandler> {
+        self.handlers.get(name)
+    }
+}
+
+pub trait RegisterProgram {
+    fn register_program(&mut self, name: &str, handler: impl Fn(&mut ProgramContext) -> i32 + Send + Sync + 'static) -> &mut Self;
+}
+
+impl RegisterProgram for App {
+    fn register_program(&mut self, name: &str, handler: impl Fn(&mut ProgramContext) -> i32 + Send + Sync + 'static) -> &mut Self {
+        self.init_resource::<ProgramRegistry>();
+        self.world.resource_mut::<ProgramRegistry>().register(name, handler);
+        self
+    }
+}
+
+/// Registers the handlers for the programs in the default filesystem
+pub struct ProgramsPlugin;
+
+impl Plugin for ProgramsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProgramRegistry>()
+            .register_program("create_server.exe", |program| {
+                let name = program.argv[0].clone();
+                program.eprint(format!("{}: network unavailable, could not create a server", name));
+                1
+            })
+            .register_program("join_server.exe", |program| {
+                let name = program.argv[0].clone();
+                program.eprint(format!("{}: network unavailable, could not join a server", name));
+                1
+            })
+            .register_program("countdown.exe", |program| {
+                let seconds = match program.argv.get(1).map(|arg| arg.parse::<u32>()) {
+                    None => 10,
+                    Some(Ok(seconds)) => seconds,
+                    Some(Err(_)) => {
+                        program.eprint("usage: countdown.exe [seconds]");
+                        return 2;
+                    }
+                };
+                program.spawn(Countdown { remaining: seconds, timer: 0.0 });
+                0
+            })
+            .register_program("download.exe", |program| {
+                let target = match program.argv.get(1) {
+                    // checked now so a download into a locked directory fails straight away
+                    Some(name) => program.shell.resolve(name)
+                        .and_then(|target| program.shell.check_write(&target).map(|_| target)),
+                    None => {
+                        program.eprint("usage: download.exe <file>");
+                        return 2;
+                    }
+                };
+                match target {
+                    Ok(target) => {
+                        program.print(format!("downloading {}", target));
+                        let owner = String::from(program.shell.user());
+                        program.spawn(Download { target, owner, progress: 0.0, reported: 0 });
+                        0
+                    }
+                    Err(err) => {
+                        program.eprint(err.to_string());
+                        1
+                    }
+                }
+            })
+            // the new floor itself is generated by the game when it sees the program has run
+            .register_program("decend.exe", |program| {
+                program.print("descending to the next floor...");
+                0
+            });
+    }
+}
+
+
+
+//                                                    BUILT IN PROCESSES
+
+/// Prints the seconds left, once a second
+struct Countdown {
+    remaining: u32,
+    timer: f32,
+}
+
+impl Process for Countdown {
+    fn tick(&mut self, ctx: &mut TickContext) -> ProcessState {
+        self.timer -= ctx.delta;
+        if self.timer > 0.0 {
+            return ProcessState::Running;
+        }
+        if self.remaining == 0 {
+            ctx.print("liftoff");
+            return ProcessState::Exited(0);
+        }
+        ctx.print(self.remaining.to_string());
+        self.remaining -= 1;
+        self.timer += 1.0;
+        ProcessState::Running
+    }
+}
+
+// how long a download takes, in seconds
+const DOWNLOAD_TIME: f32 = 5.0;
+
+/// Fills in a progress bar and then writes the file
+struct Download {
+    target: VfsPath,
+    owner: String, // who started the download, and so owns the file it writes
+    progress: f32,
+    reported: usize, // tenths already printed
+}
+
+impl Process for Download {
+    fn tick(&mut self, ctx: &mut TickContext) -> ProcessState {
+        self.progress = (self.progress + ctx.delta / DOWNLOAD_TIME).min(1.0);
+        let tenths = (self.progress * 10.0) as usize;
+        if tenths > self.reported {
+            self.reported = tenths;
+            ctx.print(format!("[{}{}] {:>3}%", "#".repeat(tenths), ".".repeat(10 - tenths), tenths * 10));
+        }
+        if self.progress < 1.0 {
+            return ProcessState::Running;
+        }
+
+        match ctx.fs.write_path(&self.target, "downloaded from the network", false, &self.owner) {
+            Ok(_) => ProcessState::Exited(0),
+            Err(err) => {
+                ctx.eprint(err.to_string());
+                ProcessState::Exited(1)
+            }
+        }
+    }
+}
+
+
+
+//                                                    RUNNING PROGRAMS
+
+/// The directories on $PATH that exist, in the order they are searched
+pub fn search_path(fs: &Vfs, env: &ShellEnv, cwd: &VfsPath, user: &str) -> Vec<NodeId> {
+    let search = env.get("PATH").unwrap_or_else(|| String::from(DEFAULT_PATH));
+    search.split(':')
+        .filter(|dir| !dir.is_empty())
+        .filter_map(|dir| VfsPath::parse(dir, cwd, user).and_then(|dir| fs.lookup_dir(&dir)).ok())
+        .collect()
+}
+
+/// Finds the program a typed name refers to. Names with a slash are paths, bare names are searched for on $PATH,
+/// where only programs are found. None if there is no such program
+pub fn find(name: &str, ctx: &CommandContext) -> Option<NodeId> {
+    if name.contains('/') {
+        return ctx.resolve(name).and_then(|path| ctx.fs.lookup(&path)).ok();
+    }
+
+    search_path(ctx.fs, ctx.env, &ctx.cwd_path(), ctx.user()).into_iter()
+        .filter_map(|dir| ctx.fs.child(dir, name))
+        .find(|id| matches!(ctx.fs.get(*id).map(|node| &node.kind), Some(NodeKind::Program)))
+}
+
+/// Runs a program node with its registered handler
+pub fn exec(id: NodeId, argv: Vec<String>, stdin: Vec<String>, ctx: &mut CommandContext) -> CommandOutput {
+    let path = ctx.fs.path_of(id);
+    let name = match ctx.fs.get(id).map(|node| (&node.kind, node.name.clone())) {
+        Some((NodeKind::Program, name)) => name,
+        Some((NodeKind::Directory { .. }, _)) => { return VfsError::IsADirectory(path).into(); }
+        _ => { return CommandOutput::error(format!("{}: not executable", path)); }
+    };
+
+    if let Err(err) = ctx.check(id, Access::EXECUTE) {
+        return err.into();
+    }
+
+    let registry = ctx.programs;
+    let handler = match registry.get(&name) {
+        Some(handler) => handler,
+        None => { return CommandOutput::error(format!("{}: not executable", path)); }
+    };
+
+    let command = argv.join(" ");
+    let args = argv.iter().skip(1).cloned().collect();
+    let mut program = ProgramContext { argv, stdin, stdout: vec![], stderr: vec![], shell: ctx, spawned: None };
+    let code = handler(&mut program);
+    program.shell.executed.push(ExecutedCommand { name, args, status: code });
+
+    let mut output = CommandOutput::ok(program.stdout);
+    output.errors = program.stderr;
+    output.status = if code == 0 { ExitStatus::Success } else { ExitStatus::Failure(code) };
+    if let (Some(process), 0) = (program.spawned, code) {
+        program.shell.processes.spawn(command, process);
+    }
+    output
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::PrimitiveTopology;
+use meshtext::{Glyph, MeshGenerator, MeshText, TextSection};
+
+
+
+static FONT_DATA: &[u8] = include_bytes!("../assets/fonts/KodeMono-Regular.ttf");
+
+// how long blinking text stays on and off, in seconds
+const BLINK_PERIOD: f32 = 0.5;
+
+/// The terminal font, loaded once, with the mesh of every glyph drawn so far
+#[derive(Resource)]
+pub struct TerminalFont {
+    generator: MeshGenerator<'static>,
+    glyphs: HashMap<char, Vec<[f32; 3]>>,
+    advance: f32, // the font is monospaced, so every glyph is this wide
+}
+
+impl Default for TerminalFont {
+    fn default() -> Self {
+        let mut generator = MeshGenerator::new(FONT_DATA);
+
+        // the gap between two of the same glyph is the advance
+        let mut width = |text: &str| -> f32 {
+            generator.generate_section(text, false, None)
+                .map(|mesh: MeshText| mesh.vertices.chunks(3).map(|c| c[0]).fold(0.0, f32::max))
+                .unwrap_or(0.0)
+        };
+        let advance = width("00") - width("0");
+
+        TerminalFont { generator, glyphs: HashMap::new(), advance }
+    }
+}
+
+impl TerminalFont {
+    fn glyph(&mut self, c: char) -> &Vec<[f32; 3]> {
+        let generator = &mut self.generator;
+        self.glyphs.entry(c).or_insert_with(|| {
+            let transform = Mat4::from_scale(Vec3::new(1f32, 1f32, 0.2f32)).to_cols_array();
+            match generator.generate_glyph(c, false, Some(&transform)) {
+                Ok(mesh) => mesh.vertices.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(),
+                // glyphs the font does not have are left blank
+                Err(_) => vec![],
+            }
+        })
+    }
+
+    /// How wide every glyph is, before the text is scaled
+    pub fn advance(&self) -> f32 {
+        self.advance
+    }
+
+    /// Builds one mesh out of glyphs placed at their columns
+    pub fn cells_mesh(&mut self, cells: &[(usize, char)]) -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        for (column, c) in cells {
+            if c.is_whitespace() {
+                continue;
+            }
+            let x = *column as f32 * self.advance;
+            positions.extend(self.glyph(*c).iter().map(|[px, py, pz]| [px + x, *py, *pz]));
+        }
+        build_mesh(positions)
+    }
+}
+
+fn build_mesh(positions: Vec<[f32; 3]>) -> Mesh {
+    let uvs = vec![[0f32, 0f32]; positions.len()];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.compute_flat_normals();
+    mesh
+}
+
+
+
+//                                                    STYLES
+
+/// The eight ANSI colors, in escape code order
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum TermColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl TermColor {
+    const ALL: [TermColor; 8] = [
+        TermColor::Black,
+        TermColor::Red,
+        TermColor::Green,
+        TermColor::Yellow,
+        TermColor::Blue,
+        TermColor::Magenta,
+        TermColor::Cyan,
+        TermColor::White,
+    ];
+
+    fn code(&self) -> u8 {
+        30 + TermColor::ALL.iter().position(|color| color == self).unwrap_or(0) as u8
+    }
+
+    fn rgb(&self, bright: bool) -> Color {
+        let (r, g, b): (f32, f32, f32) = match self {
+            TermColor::Black => (0.1, 0.1, 0.1),
+            TermColor::Red => (0.8, 0.1, 0.1),
+            TermColor::Green => (0.1, 0.8, 0.1),
+            TermColor::Yellow => (0.8, 0.7, 0.1),
+            TermColor::Blue => (0.2, 0.3, 1.0),
+            TermColor::Magenta => (0.8, 0.2, 0.8),
+            TermColor::Cyan => (0.1, 0.8, 0.8),
+            TermColor::White => (0.8, 0.8, 0.8),
+        };
+        if bright {
+            Color::rgb((r + 0.3).min(1.0), (g + 0.3).min(1.0), (b + 0.3).min(1.0))
+        } else {
+            Color::rgb(r, g, b)
+        }
+    }
+}
+
+/// How a character is drawn. No color means the terminal's own green
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct CellStyle {
+    pub color: Option<TermColor>,
+    pub bright: bool,
+    pub bold: bool,
+    pub blink: bool,
+}
+
+impl CellStyle {
+    /// The escape code that switches to this style from the default one
+    fn escape(&self) -> String {
+        let mut codes = vec![];
+        if self.bold { codes.push(1); }
+        if self.blink { codes.push(5); }
+        if let Some(color) = self.color {
+            codes.push(color.code() + if self.bright { 60 } else { 0 });
+        }
+        let codes: Vec<String> = codes.iter().map(u8::to_string).collect();
+        format!("\x1b[{}m", codes.join(";"))
+    }
+
+    fn apply(&mut self, code: u32) {
+        match code {
+            0 => { *self = CellStyle::default(); }
+            1 => { self.bold = true; }
+            5 => { self.blink = true; }
+            22 => { self.bold = false; }
+            25 => { self.blink = false; }
+            30..=37 => {
+                self.color = Some(TermColor::ALL[code as usize - 30]);
+                self.bright = false;
+            }
+            39 => { self.color = None; }
+            90..=97 => {
+                self.color = Some(TermColor::ALL[code as usize - 90]);
+                self.bright = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn rgb(&self) -> Color {
+        match self.color {
+            Some(color) => color.rgb(self.bright || self.bold),
+            None if self.bold => Color::rgb(0.5, 1.0, 0.5),
+            None => Color::rgb(0.1, 1.0, 0.1),
+        }
+    }
+}
+
+/// Wraps text in the escape codes for `color`, reset at the end
+pub fn paint(color: TermColor, text: impl Display) -> String {
+    let style = CellStyle { color: Some(color), ..default() };
+    format!("{}{}\x1b[0m", style.escape(), text)
+}
+
+/// Wraps text in the escape code for bold, reset at the end
+pub fn bold(text: impl Display) -> String {
+    let style = CellStyle { bold: true, ..default() };
+    format!("{}{}\x1b[0m", style.escape(), text)
+}
+
+/// One row of the screen, a style for every character
+pub type Row = Vec<(char, CellStyle)>;
+
+/// Reads `ESC[...m` color codes out of a line. Other escape sequences are dropped
+pub fn parse(line: &str) -> Row {
+    let mut row = vec![];
+    let mut style = CellStyle::default();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            row.push((c, style));
+            continue;
+        }
+        if chars.peek() != Some(&'[') {
+            continue;
+        }
+        chars.next();
+
+        let mut params = String::new();
+        let mut end = None;
+        for c in chars.by_ref() {
+            if c.is_ascii_digit() || c == ';' {
+                params.push(c);
+            } else {
+                end = Some(c);
+                break;
+            }
+        }
+        if end == Some('m') {
+            // an empty code, as in ESC[m, is a reset
+            for code in params.split(';') {
+                style.apply(code.parse().unwrap_or(0));
+            }
+        }
+    }
+    row
+}
+
+/// Removes color codes, for output that goes into a file or another command
+pub fn strip_styles(line: &str) -> String {
+    if !line.contains('\x1b') {
+        return String::from(line);
+    }
+    parse(line).into_iter().map(|(c, _)| c).collect()
+}
+
+/// Soft wraps a line to `columns`, breaking at the last space that fits
+/// and inside words that are too long to fit on a row of their own
+pub fn wrap(line: &str, columns: usize) -> Vec<Row> {
+    let columns = columns.max(1);
+    let cells = parse(line);
+    let mut rest = &cells[..];
+    let mut rows = vec![];
+
+    while rest.len() > columns {
+        match rest[..=columns].iter().rposition(|(c, _)| c.is_whitespace()).filter(|i| *i > 0) {
+            Some(space) => {
+                rows.push(rest[..space].to_vec());
+                rest = &rest[space + 1..];
+            }
+            None => {
+                rows.push(rest[..columns].to_vec());
+                rest = &rest[columns..];
+            }
+        }
+    }
+    rows.push(rest.to_vec());
+    rows
+}
+
+/// Groups a row's characters by style, each with its column
+pub fn spans(row: &Row) -> Vec<(CellStyle, Vec<(usize, char)>)> {
+    let mut spans: Vec<(CellStyle, Vec<(usize, char)>)> = vec![];
+    for (column, (c, style)) in row.iter().enumerate() {
+        if c.is_whitespace() {
+            continue;
+        }
+        match spans.iter_mut().find(|(span_style, _)| span_style == style) {
+            Some((_, cells)) => cells.push((column, *c)),
+            None => spans.push((*style, vec![(column, *c)])),
+        }
+    }
+    spans
+}
+
+
+
+//                                                    MATERIALS
+
+/// One material per color, shared by every span drawn in it
+#[derive(Resource, Default)]
+pub struct TerminalPalette {
+    materials: HashMap<(Option<TermColor>, bool), Handle<StandardMaterial>>,
+}
+
+impl TerminalPalette {
+    pub fn material(&mut self, style: CellStyle, materials: &mut Assets<StandardMaterial>) -> Handle<StandardMaterial> {
+        let key = (style.color, style.bright || style.bold);
+        self.materials.entry(key)
+            .or_insert_with(|| materials.add(style.rgb()))
+            .clone()
+    }
+}
+
+/// Marks a span of blinking text
+#[derive(Component)]
+pub struct Blink;
+
+pub fn blink_text(time: Res<Time>, mut blink_query: Query<&mut Visibility, With<Blink>>) {
+    let on = (time.elapsed_seconds() / BLINK_PERIOD) as u32 % 2 == 0;
+    let visibility = if on { Visibility::Inherited } else { Visibility::Hidden };
+    for mut current in blink_query.iter_mut() {
+        if *current != visibility {
+            *current = visibility;
+        }
+    }
+}
@@ -0,0 +1,686 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use bevy::prelude::*;
+use crate::commands::{CommandContext, CommandOutput, ExitStatus};
+use crate::render;
+
+
+
+/// Problems with the shape of a command line, found before anything runs
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ShellError {
+    UnterminatedQuote,
+    EmptyCommand,
+    MissingRedirectTarget,
+    RedirectNotLast,
+    Expected(&'static str),
+    Unexpected(String),
+    TooDeep,
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::UnterminatedQuote => write!(f, "unterminated quote"),
+            ShellError::EmptyCommand => write!(f, "empty command in pipeline"),
+            ShellError::MissingRedirectTarget => write!(f, "expected a file name after the redirect"),
+            ShellError::RedirectNotLast => write!(f, "a redirect can only come at the end of the line"),
+            ShellError::Expected(keyword) => write!(f, "expected '{}'", keyword),
+            ShellError::Unexpected(keyword) => write!(f, "unexpected '{}'", keyword),
+            ShellError::TooDeep => write!(f, "scripts are nested too deeply"),
+        }
+    }
+}
+
+// scripts running scripts stop here
+const MAX_SCRIPT_DEPTH: usize = 16;
+
+/// Shell variables. `set` variables belong to the script that set them,
+/// `export`ed ones are also seen by scripts it runs and are passed back up when they finish
+#[derive(Component, Clone, Default)]
+pub struct ShellEnv {
+    vars: HashMap<String, String>,
+    exported: HashSet<String>,
+    pub args: Vec<String>, // $0 is the script, $1.. its arguments
+    pub last_status: i32,
+    depth: usize,
+}
+
+impl ShellEnv {
+    pub fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status.to_string()),
+            "#" => Some(self.args.len().saturating_sub(1).to_string()),
+            "@" => Some(self.args.iter().skip(1).cloned().collect::<Vec<String>>().join(" ")),
+            _ => match name.parse::<usize>() {
+                Ok(index) => Some(self.args.get(index).cloned().unwrap_or_default()),
+                Err(_) => self.vars.get(name).cloned(),
+            },
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars.insert(String::from(name), String::from(value));
+    }
+
+    pub fn export(&mut self, name: &str, value: &str) {
+        self.set(name, value);
+        self.exported.insert(String::from(name));
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.vars.remove(name);
+        self.exported.remove(name);
+    }
+
+    /// Variables in name order, marked with whether they are exported
+    pub fn vars(&self) -> Vec<(&String, &String, bool)> {
+        let mut vars: Vec<(&String, &String, bool)> = self.vars.iter()
+            .map(|(name, value)| (name, value, self.exported.contains(name)))
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    /// The scope a script runs in: exported variables only, plus its arguments
+    pub fn child(&self, args: Vec<String>) -> Result<ShellEnv, ShellError> {
+        if self.depth >= MAX_SCRIPT_DEPTH {
+            return Err(ShellError::TooDeep);
+        }
+        let vars = self.vars.iter()
+            .filter(|(name, _)| self.exported.contains(*name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        Ok(ShellEnv { vars, exported: self.exported.clone(), args, last_status: 0, depth: self.depth + 1 })
+    }
+
+    /// Takes back the exported variables of a finished script
+    pub fn merge_exports(&mut self, child: &ShellEnv) {
+        for name in &child.exported {
+            if let Some(value) = child.vars.get(name) {
+                self.export(name, value);
+            }
+        }
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token {
+    Word(String),
+    Pipe,
+    Redirect { append: bool },
+    Background,
+}
+
+
+
+//                                                    PARSING
+
+/// Reads a variable reference after a `$`: `NAME`, `{NAME}` or one of `? # @ 0-9`
+fn read_var_name(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    match chars.peek().copied() {
+        Some('{') => {
+            chars.next();
+            let mut name = String::new();
+            while let Some(c) = chars.next() {
+                if c == '}' { break; }
+                name.push(c);
+            }
+            Some(name)
+        }
+        Some(c) if c == '?' || c == '#' || c == '@' || c.is_ascii_digit() => {
+            chars.next();
+            Some(c.to_string())
+        }
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(c) = chars.peek().copied() {
+                if !(c.is_ascii_alphanumeric() || c == '_') { break; }
+                name.push(c);
+                chars.next();
+            }
+            Some(name)
+        }
+        _ => None,
+    }
+}
+
+/// Splits a line into words and operators, expanding `$VAR`s. Quotes group words and stop `|`, `>` and `&`
+/// from being operators, single quotes are literal and a backslash escapes the next character.
+/// Unquoted variables are split into separate words on whitespace
+pub fn tokenize(line: &str, env: &ShellEnv) -> Result<Vec<Token>, ShellError> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut in_word = false; // tracks "" so an empty quoted string is still a word
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => { return Err(ShellError::UnterminatedQuote); }
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => { return Err(ShellError::UnterminatedQuote); }
+                        },
+                        Some('$') => match read_var_name(&mut chars) {
+                            Some(name) => word.push_str(&env.get(&name).unwrap_or_default()),
+                            None => word.push('$'),
+                        },
+                        Some(c) => word.push(c),
+                        None => { return Err(ShellError::UnterminatedQuote); }
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            '$' => match read_var_name(&mut chars) {
+                Some(name) => {
+                    for c in env.get(&name).unwrap_or_default().chars() {
+                        if c.is_whitespace() {
+                            if in_word {
+                                tokens.push(Token::Word(std::mem::take(&mut word)));
+                                in_word = false;
+                            }
+                        } else {
+                            in_word = true;
+                            word.push(c);
+                        }
+                    }
+                }
+                None => {
+                    in_word = true;
+                    word.push('$');
+                }
+            },
+            '|' | '>' | '&' => {
+                if in_word {
+                    tokens.push(Token::Word(std::mem::take(&mut word)));
+                    in_word = false;
+                }
+                if c == '|' {
+                    tokens.push(Token::Pipe);
+                } else if c == '&' {
+                    tokens.push(Token::Background);
+                } else if chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token::Redirect { append: true });
+                } else {
+                    tokens.push(Token::Redirect { append: false });
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    tokens.push(Token::Word(std::mem::take(&mut word)));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+/// Commands joined by pipes, with where the last one's output should go
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Pipeline {
+    pub stages: Vec<Vec<String>>,
+    pub redirect: Option<Redirect>,
+    pub background: bool, // ended with `&`
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Redirect {
+    pub target: String,
+    pub append: bool,
+}
+
+pub fn parse(line: &str, env: &ShellEnv) -> Result<Pipeline, ShellError> {
+    let tokens = tokenize(line, env)?;
+    let mut pipeline = Pipeline::default();
+    if tokens.is_empty() {
+        return Ok(pipeline);
+    }
+
+    let mut stage = vec![];
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        if pipeline.background {
+            return Err(ShellError::Unexpected(String::from("&")));
+        }
+        if pipeline.redirect.is_some() && token != Token::Background {
+            return Err(ShellError::RedirectNotLast);
+        }
+        match token {
+            Token::Word(word) => stage.push(word),
+            Token::Pipe => {
+                if stage.is_empty() {
+                    return Err(ShellError::EmptyCommand);
+                }
+                pipeline.stages.push(std::mem::take(&mut stage));
+            }
+            Token::Redirect { append } => {
+                let target = match tokens.next() {
+                    Some(Token::Word(target)) => target,
+                    _ => { return Err(ShellError::MissingRedirectTarget); }
+                };
+                pipeline.redirect = Some(Redirect { target, append });
+            }
+            Token::Background => {
+                if stage.is_empty() {
+                    return Err(ShellError::EmptyCommand);
+                }
+                pipeline.background = true;
+            }
+        }
+    }
+
+    if stage.is_empty() {
+        return Err(ShellError::EmptyCommand);
+    }
+    pipeline.stages.push(stage);
+    Ok(pipeline)
+}
+
+
+
+//                                                    SCRIPTS
+
+/// One step of a script. Pipelines are kept as written and expanded when they run
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Statement {
+    Pipeline(String),
+    If { condition: String, then: Vec<Statement>, otherwise: Vec<Statement> },
+    For { var: String, items: String, body: Vec<Statement> },
+}
+
+/// Splits a script into statements on newlines and `;`, dropping `#` comments.
+/// `then`, `else` and `do` become statements of their own so `if x; then echo y; fi` works on one line
+fn split_statements(script: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => { quote = None; current.push(c); }
+            (Some(_), '\\') => {
+                current.push(c);
+                if let Some(next) = chars.next() { current.push(next); }
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'') | (None, '"') => { quote = Some(c); current.push(c); }
+            (None, '\\') => {
+                current.push(c);
+                if let Some(next) = chars.next() { current.push(next); }
+            }
+            (None, '#') if current.is_empty() || current.ends_with(char::is_whitespace) => {
+                while let Some(next) = chars.peek() {
+                    if *next == '\n' { break; }
+                    chars.next();
+                }
+            }
+            (None, ';') | (None, '\n') => statements.push(std::mem::take(&mut current)),
+            // `&` ends a statement too, but stays on it so the pipeline knows to run in the background
+            (None, '&') => {
+                current.push(c);
+                statements.push(std::mem::take(&mut current));
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    statements.push(current);
+
+    let mut split = vec![];
+    for statement in statements {
+        let statement = statement.trim();
+        match statement.split_once(char::is_whitespace) {
+            Some((keyword, rest)) if matches!(keyword, "then" | "else" | "do") => {
+                split.push(String::from(keyword));
+                split.push(String::from(rest.trim()));
+            }
+            _ => split.push(String::from(statement)),
+        }
+    }
+    split.retain(|statement| !statement.is_empty());
+    split
+}
+
+fn keyword(statement: &str) -> (&str, &str) {
+    match statement.split_once(char::is_whitespace) {
+        Some((keyword, rest)) => (keyword, rest.trim()),
+        None => (statement, ""),
+    }
+}
+
+/// Parses statements until one of `terminators`, which is returned along with the block
+fn parse_block(statements: &[String], pos: &mut usize, terminators: &[&'static str]) -> Result<(Vec<Statement>, Option<String>), ShellError> {
+    let mut block = vec![];
+    while *pos < statements.len() {
+        let statement = &statements[*pos];
+        *pos += 1;
+        let (word, rest) = keyword(statement);
+
+        if terminators.contains(&word) {
+            return Ok((block, Some(statement.clone())));
+        }
+
+        match word {
+            "if" => block.push(parse_if(rest, statements, pos)?),
+            "for" => {
+                let (var, items) = match keyword(rest) {
+                    (var, items) if is_valid_name(var) => match keyword(items) {
+                        ("in", items) => (var, items),
+                        _ => { return Err(ShellError::Expected("in")); }
+                    },
+                    _ => { return Err(ShellError::Expected("for <name> in <words>")); }
+                };
+                if statements.get(*pos).map(String::as_str) != Some("do") {
+                    return Err(ShellError::Expected("do"));
+                }
+                *pos += 1;
+                let (body, end) = parse_block(statements, pos, &["done"])?;
+                if end.is_none() {
+                    return Err(ShellError::Expected("done"));
+                }
+                block.push(Statement::For { var: String::from(var), items: String::from(items), body });
+            }
+            "then" | "else" | "elif" | "fi" | "do" | "done" => {
+                return Err(ShellError::Unexpected(String::from(word)));
+            }
+            _ => block.push(Statement::Pipeline(statement.clone())),
+        }
+    }
+    Ok((block, None))
+}
+
+fn parse_if(condition: &str, statements: &[String], pos: &mut usize) -> Result<Statement, ShellError> {
+    if statements.get(*pos).map(String::as_str) != Some("then") {
+        return Err(ShellError::Expected("then"));
+    }
+    *pos += 1;
+
+    let (then, end) = parse_block(statements, pos, &["else", "elif", "fi"])?;
+    let otherwise = match end.as_deref().map(keyword) {
+        Some(("fi", _)) => vec![],
+        Some(("else", _)) => match parse_block(statements, pos, &["fi"])? {
+            (otherwise, Some(_)) => otherwise,
+            (_, None) => { return Err(ShellError::Expected("fi")); }
+        },
+        // elif is an if nested in the else branch, sharing the one fi
+        Some(("elif", condition)) => vec![parse_if(condition, statements, pos)?],
+        _ => { return Err(ShellError::Expected("fi")); }
+    };
+
+    Ok(Statement::If { condition: String::from(condition), then, otherwise })
+}
+
+pub fn parse_script(script: &str) -> Result<Vec<Statement>, ShellError> {
+    let statements = split_statements(script);
+    let mut pos = 0;
+    let (block, _) = parse_block(&statements, &mut pos, &[])?;
+    Ok(block)
+}
+
+
+
+//                                                    RUNNING
+
+/// Runs a command line or a whole script
+pub fn run(script: &str, ctx: &mut CommandContext) -> CommandOutput {
+    match parse_script(script) {
+        Ok(statements) => run_block(&statements, ctx),
+        Err(err) => CommandOutput::error(err.to_string()),
+    }
+}
+
+/// Runs a script in its own scope with `args` as $0, $1, ...
+pub fn run_script(script: &str, args: Vec<String>, ctx: &mut CommandContext) -> CommandOutput {
+    let mut scope = match ctx.env.child(args) {
+        Ok(scope) => scope,
+        Err(err) => { return CommandOutput::error(err.to_string()); }
+    };
+
+    std::mem::swap(ctx.env, &mut scope);
+    let output = run(script, ctx);
+    std::mem::swap(ctx.env, &mut scope);
+    ctx.env.merge_exports(&scope);
+    output
+}
+
+fn run_block(block: &[Statement], ctx: &mut CommandContext) -> CommandOutput {
+    let mut output = CommandOutput::empty();
+    for statement in block {
+        let step = match statement {
+            Statement::Pipeline(line) => run_pipeline(line, ctx),
+            Statement::If { condition, then, otherwise } => {
+                let mut step = run_pipeline(condition, ctx);
+                let branch = if step.status == ExitStatus::Success { then } else { otherwise };
+                let body = run_block(branch, ctx);
+                step.lines.extend(body.lines);
+                step.errors.extend(body.errors);
+                step.actions.extend(body.actions);
+                step.status = body.status;
+                step
+            }
+            Statement::For { var, items, body } => {
+                let words: Vec<String> = match tokenize(items, ctx.env) {
+                    Ok(tokens) => tokens.into_iter().filter_map(|token| match token {
+                        Token::Word(word) => Some(word),
+                        _ => None,
+                    }).collect(),
+                    Err(err) => { return CommandOutput::error(err.to_string()); }
+                };
+                let mut step = CommandOutput::empty();
+                for word in words {
+                    ctx.env.set(var, &word);
+                    let iteration = run_block(body, ctx);
+                    step.lines.extend(iteration.lines);
+                    step.errors.extend(iteration.errors);
+                    step.actions.extend(iteration.actions);
+                    step.status = iteration.status;
+                }
+                step
+            }
+        };
+
+        output.lines.extend(step.lines);
+        output.errors.extend(step.errors);
+        output.actions.extend(step.actions);
+        output.status = step.status;
+    }
+    output
+}
+
+/// Runs one pipeline. Each stage's output is the next stage's input,
+/// errors from every stage are kept and the exit status is the last stage's
+fn run_pipeline(line: &str, ctx: &mut CommandContext) -> CommandOutput {
+    let pipeline = match parse(line, ctx.env) {
+        Ok(pipeline) => pipeline,
+        Err(err) => { return CommandOutput::error(err.to_string()); }
+    };
+
+    let first_pid = ctx.processes.next_pid();
+    let mut output = CommandOutput::empty();
+    for argv in pipeline.stages {
+        // colors are for the screen, the next command gets plain text
+        let stdin = output.lines.drain(..).map(|line| render::strip_styles(&line)).collect();
+        let registry = ctx.registry;
+        let stage = registry.run(argv, stdin, ctx);
+        output.lines = stage.lines;
+        output.errors.extend(stage.errors);
+        output.actions.extend(stage.actions);
+        output.status = stage.status;
+    }
+
+    if let Some(redirect) = pipeline.redirect {
+        let written = ctx.resolve(&redirect.target)
+            .and_then(|path| {
+                let lines: Vec<String> = output.lines.iter().map(|line| render::strip_styles(line)).collect();
+                ctx.write_path(&path, &lines.join("\n"), redirect.append)
+            });
+        match written {
+            Ok(_) => { output.lines.clear(); }
+            Err(err) => { output.push_error(err.to_string()); }
+        }
+    }
+
+    // processes started by a background pipeline let the prompt come straight back
+    if pipeline.background {
+        for pid in first_pid..ctx.processes.next_pid() {
+            if let Some(entry) = ctx.processes.get(pid) {
+                output.lines.push(format!("[{}] {}", pid, entry.command));
+                ctx.processes.set_background(pid);
+            }
+        }
+    }
+
+    ctx.env.last_status = output.status.code();
+    output
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str, env: &ShellEnv) -> Vec<String> {
+        tokenize(line, env).unwrap().into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                token => panic!("expected only words, got {:?}", token),
+            })
+            .collect()
+    }
+
+    fn pipeline(line: &str) -> Statement {
+        Statement::Pipeline(String::from(line))
+    }
+
+    #[test]
+    fn tokenize_groups_quotes_and_escapes() {
+        let env = ShellEnv::default();
+        assert_eq!(words(r#"echo "a | b" 'c > d' e\ f "" x"y"z"#, &env), ["echo", "a | b", "c > d", "e f", "", "xyz"]);
+        assert_eq!(words(r#"echo "say \"hi\"" '$HOME' \$HOME"#, &env), ["echo", "say \"hi\"", "$HOME", "$HOME"]);
+        assert_eq!(tokenize("echo 'open", &env), Err(ShellError::UnterminatedQuote));
+        assert_eq!(tokenize("echo \"open", &env), Err(ShellError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn tokenize_expands_variables() {
+        let mut env = ShellEnv::default();
+        env.set("NAME", "sumi");
+        env.set("LIST", "a  b c");
+        env.args = vec![String::from("script.sh"), String::from("one"), String::from("two")];
+        assert_eq!(words("echo $NAME ${NAME}s $MISSING end", &env), ["echo", "sumi", "sumis", "end"]);
+        assert_eq!(words("for $LIST \"$LIST\"", &env), ["for", "a", "b", "c", "a  b c"]);
+        assert_eq!(words("echo $0 $1 $# \"$@\" $", &env), ["echo", "script.sh", "one", "2", "one two", "$"]);
+    }
+
+    #[test]
+    fn parse_splits_pipelines() {
+        let env = ShellEnv::default();
+        let parsed = parse("cat a.txt | grep x|sort >> out.txt &", &env).unwrap();
+        assert_eq!(parsed.stages, [vec!["cat", "a.txt"], vec!["grep", "x"], vec!["sort"]]);
+        assert_eq!(parsed.redirect, Some(Redirect { target: String::from("out.txt"), append: true }));
+        assert!(parsed.background);
+
+        let parsed = parse("echo hi>out.txt", &env).unwrap();
+        assert_eq!(parsed.stages, [vec!["echo", "hi"]]);
+        assert_eq!(parsed.redirect, Some(Redirect { target: String::from("out.txt"), append: false }));
+        assert!(!parsed.background);
+
+        assert_eq!(parse("   ", &env), Ok(Pipeline::default()));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_lines() {
+        let env = ShellEnv::default();
+        assert_eq!(parse("ls |", &env), Err(ShellError::EmptyCommand));
+        assert_eq!(parse("| ls", &env), Err(ShellError::EmptyCommand));
+        assert_eq!(parse("ls || wc", &env), Err(ShellError::EmptyCommand));
+        assert_eq!(parse("echo >", &env), Err(ShellError::MissingRedirectTarget));
+        assert_eq!(parse("echo > | wc", &env), Err(ShellError::MissingRedirectTarget));
+        assert_eq!(parse("echo > a.txt | wc", &env), Err(ShellError::RedirectNotLast));
+        assert_eq!(parse("sleep 1 & ls", &env), Err(ShellError::Unexpected(String::from("&"))));
+        assert_eq!(parse("&", &env), Err(ShellError::EmptyCommand));
+    }
+
+    #[test]
+    fn parse_script_reads_statements() {
+        let script = "# a comment\necho one; echo 'two; three' # trailing\nsleep 5 & echo four\n\n";
+        assert_eq!(parse_script(script), Ok(vec![
+            pipeline("echo one"),
+            pipeline("echo 'two; three'"),
+            pipeline("sleep 5 &"),
+            pipeline("echo four"),
+        ]));
+        assert_eq!(parse_script("echo a#b"), Ok(vec![pipeline("echo a#b")]));
+    }
+
+    #[test]
+    fn parse_script_reads_blocks() {
+        let script = "if test -f a\nthen\n  echo a\nelif test -f b; then echo b\nelse\n  for x in $@; do echo $x; done\nfi";
+        assert_eq!(parse_script(script), Ok(vec![Statement::If {
+            condition: String::from("test -f a"),
+            then: vec![pipeline("echo a")],
+            otherwise: vec![Statement::If {
+                condition: String::from("test -f b"),
+                then: vec![pipeline("echo b")],
+                otherwise: vec![Statement::For {
+                    var: String::from("x"),
+                    items: String::from("$@"),
+                    body: vec![pipeline("echo $x")],
+                }],
+            }],
+        }]));
+        assert_eq!(parse_script("if true; then echo y; fi"), Ok(vec![Statement::If {
+            condition: String::from("true"),
+            then: vec![pipeline("echo y")],
+            otherwise: vec![],
+        }]));
+    }
+
+    #[test]
+    fn parse_script_rejects_unbalanced_blocks() {
+        assert_eq!(parse_script("if true; echo y; fi"), Err(ShellError::Expected("then")));
+        assert_eq!(parse_script("if true; then echo y"), Err(ShellError::Expected("fi")));
+        assert_eq!(parse_script("if true; then echo y; else echo n"), Err(ShellError::Expected("fi")));
+        assert_eq!(parse_script("for x in a b; echo $x; done"), Err(ShellError::Expected("do")));
+        assert_eq!(parse_script("for x in a b; do echo $x"), Err(ShellError::Expected("done")));
+        assert_eq!(parse_script("for 1x in a; do echo; done"), Err(ShellError::Expected("for <name> in <words>")));
+        assert_eq!(parse_script("for x of a; do echo; done"), Err(ShellError::Expected("in")));
+        assert_eq!(parse_script("echo y; fi"), Err(ShellError::Unexpected(String::from("fi"))));
+    }
+}
@@ -0,0 +1,24 @@
+use std::env;
+use std::path::PathBuf;
+
+
+
+/// The per user directory oxyb keeps its files in (terminal history, saves).
+/// Follows the platform convention and falls back to the working directory
+pub fn data_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+
+    base.unwrap_or_else(|| PathBuf::from(".")).join("oxyb")
+}
+
+/// Turns an id like a console name into something safe to use as a file name
+pub fn file_name(id: &str) -> String {
+    id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
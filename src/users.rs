@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use crate::vfs::DEFAULT_USER;
+
+
+
+/// The superuser, who may read, write and run anything
+pub const ROOT_USER: &str = "root";
+
+/// A simulated account. Players find the passwords lying around the filesystem
+pub struct Account {
+    pub name: String,
+    password: Option<String>, // no password means anyone can log in
+}
+
+impl Account {
+    pub fn new(name: &str, password: Option<&str>) -> Self {
+        Account { name: String::from(name), password: password.map(String::from) }
+    }
+
+    pub fn needs_password(&self) -> bool {
+        self.password.is_some()
+    }
+
+    pub fn check_password(&self, attempt: &str) -> bool {
+        self.password.as_deref().map_or(true, |password| password == attempt)
+    }
+}
+
+/// Every account on the machine, shared by all consoles
+#[derive(Resource)]
+pub struct Users {
+    accounts: Vec<Account>,
+}
+
+impl Default for Users {
+    fn default() -> Self {
+        // keep these in step with the notes in assets/terminal/default.vfs.json
+        Users {
+            accounts: vec![
+                Account::new(ROOT_USER, Some("ashfall")),
+                Account::new(DEFAULT_USER, None),
+                Account::new("kai", Some("moonlight")),
+            ],
+        }
+    }
+}
+
+impl Users {
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.iter().find(|account| account.name == name)
+    }
+}
+
+
+
+//                                                    SESSIONS
+
+/// A login waiting for its password to be typed
+pub struct PasswordPrompt {
+    pub user: String,
+    pub switch: bool, // true for su, which can be undone with logout
+    pub input: String,
+}
+
+impl PasswordPrompt {
+    /// What the prompt line shows. The password itself is never drawn
+    pub fn label(&self) -> String {
+        format!("password for {}: ", self.user)
+    }
+}
+
+/// Who is logged in on a console. `su` stacks users on top of the login so `logout` can go back
+#[derive(Component)]
+pub struct Session {
+    user: String,
+    previous: Vec<String>,
+    pub prompt: Option<PasswordPrompt>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session { user: String::from(DEFAULT_USER), previous: vec![], prompt: None }
+    }
+}
+
+impl Session {
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.user == ROOT_USER
+    }
+
+    /// Replaces the whole session with a fresh login
+    pub fn login(&mut self, user: &str) {
+        self.user = String::from(user);
+        self.previous.clear();
+    }
+
+    /// Becomes another user until the next logout
+    pub fn switch(&mut self, user: &str) {
+        let previous = std::mem::replace(&mut self.user, String::from(user));
+        self.previous.push(previous);
+    }
+
+    /// Goes back to the user before the last su. Returns false if there was none
+    pub fn logout(&mut self) -> bool {
+        match self.previous.pop() {
+            Some(previous) => {
+                self.user = previous;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Logs in as `user` if the password is right, either replacing the session or stacking on it
+    pub fn authenticate(&mut self, users: &Users, user: &str, password: &str, switch: bool) -> Result<(), String> {
+        match users.get(user) {
+            Some(account) if account.check_password(password) => {
+                if switch { self.switch(user); } else { self.login(user); }
+                Ok(())
+            }
+            _ => Err(String::from("login incorrect")),
+        }
+    }
+
+    /// Answers the waiting password prompt with what was typed into it. Returns the user now logged in
+    pub fn submit_password(&mut self, users: &Users) -> Result<String, String> {
+        let prompt = self.prompt.take().ok_or("no login is waiting for a password")?;
+        self.authenticate(users, &prompt.user, &prompt.input, prompt.switch)?;
+        Ok(prompt.user)
+    }
+}
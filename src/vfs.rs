@@ -0,0 +1,975 @@
+use std::fmt;
+use std::ops::BitOr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::prelude::*;
+use crate::console::{Directory, File, Node, Program};
+use crate::users::ROOT_USER;
+
+
+
+/// The user every console is logged in as when it starts
+pub const DEFAULT_USER: &str = "sumi";
+
+/// Errors from resolving and following paths in the terminal filesystem
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VfsError {
+    EmptyPath,
+    NotFound(VfsPath),
+    NotADirectory(VfsPath),
+    AlreadyExists(VfsPath),
+    InvalidName(String),
+    IsADirectory(VfsPath),
+    NotAFile(VfsPath),
+    IsRoot,
+    IntoItself(VfsPath),
+    PermissionDenied(VfsPath),
+}
+
+impl fmt::Display for VfsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VfsError::EmptyPath => write!(f, "empty path"),
+            VfsError::NotFound(path) => write!(f, "{}: no such file or directory", path),
+            VfsError::NotADirectory(path) => write!(f, "{}: not a directory", path),
+            VfsError::AlreadyExists(path) => write!(f, "{}: already exists", path),
+            VfsError::InvalidName(name) => write!(f, "'{}' is not a valid name", name),
+            VfsError::IsADirectory(path) => write!(f, "{}: is a directory", path),
+            VfsError::NotAFile(path) => write!(f, "{}: is a program, not a file", path),
+            VfsError::IsRoot => write!(f, "/: cannot change the root directory"),
+            VfsError::IntoItself(path) => write!(f, "{}: cannot move a directory into itself", path),
+            VfsError::PermissionDenied(path) => write!(f, "{}: permission denied", path),
+        }
+    }
+}
+
+
+
+//                                                    DEFINITIONS
+
+/// The tree the game ships with, used until the asset has loaded or when it cannot be
+static BUILT_IN_DEFINITION: &[u8] = include_bytes!("../assets/terminal/default.vfs.json");
+
+/// Why a filesystem definition could not be turned into a tree
+#[derive(Debug)]
+pub enum DefinitionError {
+    Read(std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(Vec<String>), // one message per bad node, each starting with its path
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DefinitionError::Read(err) => write!(f, "could not read the definition: {}", err),
+            DefinitionError::Parse(err) => write!(f, "could not parse the definition: {}", err),
+            DefinitionError::Invalid(problems) => write!(f, "invalid definition:\n  {}", problems.join("\n  ")),
+        }
+    }
+}
+
+impl std::error::Error for DefinitionError {}
+
+/// Parses a definition and checks every node in it, so a bad tree is rejected as a whole
+pub fn parse_definition(bytes: &[u8]) -> Result<Directory, DefinitionError> {
+    let dir: Directory = serde_json::from_slice(bytes).map_err(DefinitionError::Parse)?;
+    let mut problems = vec![];
+    validate(&dir, &VfsPath::root(), &mut problems);
+    if problems.is_empty() {
+        Ok(dir)
+    } else {
+        Err(DefinitionError::Invalid(problems))
+    }
+}
+
+/// The definition built into the game. Should even that fail to parse there is still a home to log in to
+pub fn built_in_definition() -> Directory {
+    parse_definition(BUILT_IN_DEFINITION).unwrap_or_else(|err| {
+        error!("the built in filesystem is broken: {}", err);
+        let mut home = Directory::new(String::from(DEFAULT_USER));
+        home.owner = Some(String::from(DEFAULT_USER));
+        let mut users = Directory::new(String::from("users"));
+        users.children.insert(String::from("0"), Node::Directory(home));
+        let mut root = Directory::new(String::from("root"));
+        root.children.insert(String::from("0"), Node::Directory(users));
+        root
+    })
+}
+
+/// A directory's children in the order they are listed. They are keyed "0", "1", ... in the json
+fn ordered_children(dir: &Directory) -> Vec<(&String, &Node)> {
+    let mut children: Vec<(&String, &Node)> = dir.children.iter().collect();
+    children.sort_by_key(|(key, _)| (key.len(), key.as_str()));
+    children
+}
+
+/// Every node under `dir` with its path, parents before their children
+fn flatten<'a>(dir: &'a Directory, path: &VfsPath, nodes: &mut Vec<(VfsPath, &'a Node)>) {
+    for (_, node) in ordered_children(dir) {
+        let node_path = path.join(&node.display_name());
+        nodes.push((node_path.clone(), node));
+        if let Node::Directory(child) = node {
+            flatten(child, &node_path, nodes);
+        }
+    }
+}
+
+fn node_kind(node: &Node) -> NodeKind {
+    match node {
+        Node::Directory(_) => NodeKind::Directory { children: vec![] },
+        Node::File(file) => NodeKind::File { content: file.content.clone() },
+        Node::Program(_) => NodeKind::Program,
+    }
+}
+
+/// True if a node is new to a definition, or the definition changed the node itself rather than only what is under it
+fn definition_changed(node: &Node, previous: Option<&Node>) -> bool {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => { return true; }
+    };
+    let same = match (node, previous) {
+        (Node::File(new), Node::File(old)) => new.content == old.content,
+        (Node::Directory(_), Node::Directory(_)) | (Node::Program(_), Node::Program(_)) => true,
+        _ => false,
+    };
+    !same || node.access() != previous.access()
+}
+
+fn validate(dir: &Directory, path: &VfsPath, problems: &mut Vec<String>) {
+    let mut seen = vec![];
+    for (key, node) in ordered_children(dir) {
+        let name = node.display_name();
+        let node_path = path.join(&name);
+        if name.is_empty() || name == "." || name == ".." {
+            problems.push(format!("{}: child '{}' has no usable name", path, key));
+            continue;
+        }
+        if seen.contains(&name) {
+            problems.push(format!("{}: more than one child is named '{}'", path, name));
+        }
+        if let (_, Some(permissions)) = node.access() {
+            if Permissions::parse(permissions).is_none() {
+                problems.push(format!("{}: '{}' is not a permission string like rwxr-x", node_path, permissions));
+            }
+        }
+        if let Node::Directory(child) = node {
+            validate(child, &node_path, problems);
+        }
+        seen.push(name);
+    }
+}
+
+
+
+//                                                    PATHS
+
+/// An absolute, normalised path in the terminal filesystem. Display form is `/files/data.txt`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct VfsPath {
+    segments: Vec<String>,
+}
+
+impl VfsPath {
+    pub fn root() -> Self {
+        VfsPath { segments: vec![] }
+    }
+
+    /// The home directory of a user, `/users/<name>`
+    pub fn home(user: &str) -> Self {
+        VfsPath::root().join("users").join(user)
+    }
+
+    /// Resolves what the player typed against the current directory.
+    /// Handles absolute paths, `.`, `..` (which stops at the root), `~` and `~user`
+    pub fn parse(input: &str, cwd: &VfsPath, user: &str) -> Result<VfsPath, VfsError> {
+        if input.is_empty() {
+            return Err(VfsError::EmptyPath);
+        }
+
+        let (mut path, rest) = if let Some(rest) = input.strip_prefix('/') {
+            (VfsPath::root(), rest)
+        } else if let Some(rest) = input.strip_prefix('~') {
+            let (name, rest) = rest.split_once('/').unwrap_or((rest, ""));
+            let name = if name.is_empty() { user } else { name };
+            (VfsPath::home(name), rest)
+        } else {
+            (cwd.clone(), input)
+        };
+
+        for segment in rest.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => { path.segments.pop(); }
+                name => { path.segments.push(String::from(name)); }
+            }
+        }
+
+        Ok(path)
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    pub fn parent(&self) -> Option<VfsPath> {
+        let (_, parent) = self.segments.split_last()?;
+        Some(VfsPath { segments: parent.to_vec() })
+    }
+
+    pub fn file_name(&self) -> Option<&str> {
+        self.segments.last().map(String::as_str)
+    }
+
+    pub fn join(&self, name: &str) -> VfsPath {
+        let mut path = self.clone();
+        path.segments.push(String::from(name));
+        path
+    }
+
+    /// The first `len` segments of this path
+    fn prefix(&self, len: usize) -> VfsPath {
+        VfsPath { segments: self.segments[..len].to_vec() }
+    }
+}
+
+impl fmt::Display for VfsPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, "/");
+        }
+        for segment in &self.segments {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+
+
+//                                                    NODE ARENA
+
+/// Stable handle to a node in a [`Vfs`]. Ids are never reused, so a handle to a removed node stays dead
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug)]
+pub enum NodeKind {
+    Directory { children: Vec<NodeId> },
+    File { content: String },
+    Program,
+}
+
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    pub created: u64,  // unix seconds
+    pub modified: u64, // unix seconds
+    pub owner: String,
+    pub permissions: Permissions,
+}
+
+impl Metadata {
+    fn now(owner: &str, kind: &NodeKind) -> Self {
+        let now = unix_now();
+        Metadata { created: now, modified: now, owner: String::from(owner), permissions: Permissions::default_for(kind) }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+
+
+
+//                                                    PERMISSIONS
+
+/// Read, write and execute bits, as in unix. On a directory, read lists it,
+/// write adds and removes entries and execute lets paths go through it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Access(u8);
+
+impl Access {
+    pub const NONE: Access = Access(0);
+    pub const READ: Access = Access(4);
+    pub const WRITE: Access = Access(2);
+    pub const EXECUTE: Access = Access(1);
+    pub const ALL: Access = Access(7);
+
+    pub fn contains(&self, other: Access) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Reads `rwx`, `r-x`, `---` and the like
+    fn parse(text: &str) -> Option<Access> {
+        let bits: Vec<char> = text.chars().collect();
+        if bits.len() != 3 {
+            return None;
+        }
+        let mut access = Access::NONE;
+        for (bit, (letter, value)) in bits.iter().zip([('r', Access::READ), ('w', Access::WRITE), ('x', Access::EXECUTE)]) {
+            match bit {
+                '-' => {}
+                c if *c == letter => access = access | value,
+                _ => { return None; }
+            }
+        }
+        Some(access)
+    }
+}
+
+impl BitOr for Access {
+    type Output = Access;
+
+    fn bitor(self, other: Access) -> Access {
+        Access(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (letter, bit) in [('r', Access::READ), ('w', Access::WRITE), ('x', Access::EXECUTE)] {
+            write!(f, "{}", if self.contains(bit) { letter } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+/// What a node's owner and everyone else may do with it. Displays as `rwxr-x`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Permissions {
+    pub owner: Access,
+    pub other: Access,
+}
+
+impl Permissions {
+    fn default_for(kind: &NodeKind) -> Self {
+        match kind {
+            NodeKind::File { .. } => Permissions { owner: Access::READ | Access::WRITE, other: Access::READ },
+            _ => Permissions { owner: Access::ALL, other: Access::READ | Access::EXECUTE },
+        }
+    }
+
+    /// Reads the six letter form, `rw-r--`
+    pub fn parse(text: &str) -> Option<Permissions> {
+        if text.len() != 6 || !text.is_ascii() {
+            return None;
+        }
+        let (owner, other) = text.split_at(3);
+        Some(Permissions { owner: Access::parse(owner)?, other: Access::parse(other)? })
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.owner, self.other)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VfsNode {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub kind: NodeKind,
+    pub meta: Metadata,
+}
+
+impl VfsNode {
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, NodeKind::Directory { .. })
+    }
+
+    /// What `user` may do with this node
+    pub fn access(&self, user: &str) -> Access {
+        if user == ROOT_USER {
+            Access::ALL
+        } else if self.meta.owner == user {
+            self.meta.permissions.owner
+        } else {
+            self.meta.permissions.other
+        }
+    }
+}
+
+/// The live terminal filesystem. Nodes sit in one arena and refer to each other by id,
+/// so anything holding a [`NodeId`] sees changes made through any other handle
+#[derive(Clone, Debug)]
+pub struct Vfs {
+    nodes: Vec<Option<VfsNode>>,
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Vfs::new()
+    }
+}
+
+impl Vfs {
+    pub const ROOT: NodeId = NodeId(0);
+
+    pub fn new() -> Self {
+        let kind = NodeKind::Directory { children: vec![] };
+        let root = VfsNode { name: String::new(), parent: None, meta: Metadata::now(ROOT_USER, &kind), kind };
+        Vfs { nodes: vec![Some(root)] }
+    }
+
+    /// Builds the arena from a directory tree such as the one in `assets/terminal/default.vfs.json`.
+    /// Nodes without an owner or permissions in the tree get their parent's owner and the defaults,
+    /// nodes without times are created now
+    pub fn from_directory(dir: &Directory) -> Self {
+        let mut vfs = Vfs::new();
+        vfs.add_children(Vfs::ROOT, dir);
+        vfs
+    }
+
+    fn add_children(&mut self, parent: NodeId, dir: &Directory) {
+        for (_, node) in ordered_children(dir) {
+            match self.create_inherited(parent, &node.display_name(), node_kind(node)) {
+                Ok(id) => {
+                    let (owner, permissions) = node.access();
+                    self.apply_access(id, owner, permissions);
+                    if let Node::Directory(child) = node {
+                        self.add_children(id, child);
+                    }
+                    // after the children, which would otherwise touch their directory
+                    self.apply_times(id, node.times());
+                }
+                Err(err) => warn!("skipping vfs node: {}", err),
+            }
+        }
+    }
+
+    /// The tree in its serialized form, the other way round from [`Vfs::from_directory`].
+    /// Every node is written with its owner, permissions and times so nothing is lost on the way back
+    pub fn to_directory(&self) -> Directory {
+        let mut root = Directory::new(String::from("root"));
+        self.add_to_directory(Vfs::ROOT, &mut root);
+        root
+    }
+
+    fn add_to_directory(&self, id: NodeId, dir: &mut Directory) {
+        for (i, child) in self.children(id).iter().enumerate() {
+            let node = match self.get(*child) {
+                Some(node) => node,
+                None => continue,
+            };
+            let (owner, permissions) = (Some(node.meta.owner.clone()), Some(node.meta.permissions.to_string()));
+            let (created, modified) = (Some(node.meta.created), Some(node.meta.modified));
+            let serialized = match &node.kind {
+                NodeKind::Directory { .. } => {
+                    let mut child_dir = Directory::new(node.name.clone());
+                    self.add_to_directory(*child, &mut child_dir);
+                    Node::Directory(Directory { owner, permissions, created, modified, ..child_dir })
+                }
+                NodeKind::File { content } => Node::File(File {
+                    name: node.name.clone(),
+                    content: content.clone(),
+                    owner,
+                    permissions,
+                    created,
+                    modified,
+                }),
+                NodeKind::Program => Node::Program(Program { name: node.name.clone(), owner, permissions, created, modified }),
+            };
+            dir.children.insert(i.to_string(), serialized);
+        }
+    }
+
+    /// Sets the owner and permissions a definition gives a node, where it gives them
+    fn apply_access(&mut self, id: NodeId, owner: Option<&str>, permissions: Option<&str>) {
+        if let Some(owner) = owner {
+            self.set_owner(id, owner);
+        }
+        if let Some(text) = permissions {
+            match Permissions::parse(text) {
+                Some(permissions) => self.set_permissions(id, permissions),
+                None => warn!("bad permissions '{}' on {}", text, self.path_of(id)),
+            }
+        }
+    }
+
+    /// Sets the times a saved tree recorded for a node
+    fn apply_times(&mut self, id: NodeId, (created, modified): (Option<u64>, Option<u64>)) {
+        if let Some(node) = self.get_mut(id) {
+            node.meta.created = created.unwrap_or(node.meta.created);
+            node.meta.modified = modified.unwrap_or(node.meta.modified);
+        }
+    }
+
+    /// Brings the tree from one version of its definition to the next. Only what the definition changed
+    /// is applied: nodes the player created stay, nodes they deleted or moved stay gone, and so do the player's
+    /// edits to files the new version leaves alone. Removed directories that still hold the player's files are kept
+    pub fn merge_definition(&mut self, old: &Directory, new: &Directory) {
+        let (mut old_nodes, mut new_nodes) = (vec![], vec![]);
+        flatten(old, &VfsPath::root(), &mut old_nodes);
+        flatten(new, &VfsPath::root(), &mut new_nodes);
+
+        // children go before their parents, so a directory is only looked at once it has been emptied
+        for (path, _) in old_nodes.iter().rev() {
+            if new_nodes.iter().any(|(new_path, _)| new_path == path) {
+                continue;
+            }
+            if let Ok(id) = self.lookup(path) {
+                if self.children(id).is_empty() {
+                    let _ = self.remove(id);
+                }
+            }
+        }
+
+        for (path, node) in &new_nodes {
+            let previous = old_nodes.iter().find(|(old_path, _)| old_path == path).map(|(_, node)| *node);
+            self.merge_node(path, node, previous);
+        }
+    }
+
+    fn merge_node(&mut self, path: &VfsPath, node: &Node, previous: Option<&Node>) {
+        let id = match self.lookup(path) {
+            Ok(id) => id,
+            // the player deleted or moved it, which stands unless this version of the definition changed it
+            Err(VfsError::NotFound(_)) if !definition_changed(node, previous) => { return; }
+            Err(VfsError::NotFound(_)) => {
+                let created = path.parent()
+                    .ok_or(VfsError::IsRoot)
+                    .and_then(|parent| self.lookup_dir(&parent))
+                    .and_then(|parent| self.create_inherited(parent, &node.display_name(), node_kind(node)));
+                match created {
+                    Ok(id) => {
+                        let (owner, permissions) = node.access();
+                        self.apply_access(id, owner, permissions);
+                    }
+                    Err(err) => warn!("could not add {}: {}", path, err),
+                }
+                return;
+            }
+            Err(err) => {
+                warn!("could not update {}: {}", path, err);
+                return;
+            }
+        };
+
+        if let Node::File(file) = node {
+            let changed = match previous {
+                Some(Node::File(old)) => old.content != file.content,
+                _ => true,
+            };
+            if changed {
+                if let Err(err) = self.write_file(id, &file.content, false) {
+                    warn!("could not update {}: {}", path, err);
+                }
+            }
+        }
+
+        let (owner, permissions) = node.access();
+        let (old_owner, old_permissions) = previous.map_or((None, None), Node::access);
+        self.apply_access(
+            id,
+            owner.filter(|owner| Some(*owner) != old_owner),
+            permissions.filter(|permissions| Some(*permissions) != old_permissions),
+        );
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&VfsNode> {
+        self.nodes.get(id.0).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut VfsNode> {
+        self.nodes.get_mut(id.0).and_then(Option::as_mut)
+    }
+
+    pub fn exists(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Children of a directory in creation order. Empty for anything that is not a directory
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match self.get(id).map(|node| &node.kind) {
+            Some(NodeKind::Directory { children }) => children,
+            _ => &[],
+        }
+    }
+
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        self.children(dir).iter().copied().find(|id| self.get(*id).map_or(false, |node| node.name == name))
+    }
+
+    /// The absolute path of a node, found by walking up the parent links
+    pub fn path_of(&self, id: NodeId) -> VfsPath {
+        let mut segments = vec![];
+        let mut current = self.get(id);
+        while let Some(node) = current {
+            if let Some(parent) = node.parent {
+                segments.push(node.name.clone());
+                current = self.get(parent);
+            } else {
+                break;
+            }
+        }
+        segments.reverse();
+        VfsPath { segments }
+    }
+
+    /// Follows an absolute path from the root
+    pub fn lookup(&self, path: &VfsPath) -> Result<NodeId, VfsError> {
+        let mut current = Vfs::ROOT;
+        for (i, segment) in path.segments().iter().enumerate() {
+            if !self.get(current).map_or(false, VfsNode::is_dir) {
+                return Err(VfsError::NotADirectory(path.prefix(i)));
+            }
+            current = self.child(current, segment).ok_or_else(|| VfsError::NotFound(path.prefix(i + 1)))?;
+        }
+        Ok(current)
+    }
+
+    /// Like [`Vfs::lookup`] but the path has to end at a directory
+    pub fn lookup_dir(&self, path: &VfsPath) -> Result<NodeId, VfsError> {
+        let id = self.lookup(path)?;
+        match self.get(id) {
+            Some(node) if node.is_dir() => Ok(id),
+            _ => Err(VfsError::NotADirectory(path.clone())),
+        }
+    }
+
+    /// Whether `user` may access a node in this way. Reaching a node also takes
+    /// execute permission on every directory above it, so a locked directory hides everything inside
+    pub fn check_access(&self, id: NodeId, user: &str, access: Access) -> Result<(), VfsError> {
+        let node = self.get(id).ok_or_else(|| VfsError::NotFound(self.path_of(id)))?;
+        let mut ancestor = node.parent;
+        while let Some(dir) = ancestor {
+            let dir_node = match self.get(dir) {
+                Some(dir_node) => dir_node,
+                None => break,
+            };
+            if !dir_node.access(user).contains(Access::EXECUTE) {
+                return Err(VfsError::PermissionDenied(self.path_of(dir)));
+            }
+            ancestor = dir_node.parent;
+        }
+        if !node.access(user).contains(access) {
+            return Err(VfsError::PermissionDenied(self.path_of(id)));
+        }
+        Ok(())
+    }
+
+    pub fn set_owner(&mut self, id: NodeId, owner: &str) {
+        if let Some(node) = self.get_mut(id) {
+            node.meta.owner = String::from(owner);
+        }
+    }
+
+    pub fn set_permissions(&mut self, id: NodeId, permissions: Permissions) {
+        if let Some(node) = self.get_mut(id) {
+            node.meta.permissions = permissions;
+        }
+    }
+
+    /// Adds a node under `parent`, belonging to `owner`. Names must be unique within a directory
+    pub fn create(&mut self, parent: NodeId, name: &str, kind: NodeKind, owner: &str) -> Result<NodeId, VfsError> {
+        self.check_new_child(parent, name)?;
+
+        let meta = Metadata::now(owner, &kind);
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(VfsNode { name: String::from(name), parent: Some(parent), kind, meta }));
+        if let Some(NodeKind::Directory { children }) = self.get_mut(parent).map(|node| &mut node.kind) {
+            children.push(id);
+        }
+        self.touch(parent);
+        Ok(id)
+    }
+
+    /// Adds a node from a definition, which belongs to whoever owns `parent` unless the definition says otherwise
+    fn create_inherited(&mut self, parent: NodeId, name: &str, kind: NodeKind) -> Result<NodeId, VfsError> {
+        let owner = self.get(parent).map(|node| node.meta.owner.clone()).unwrap_or_else(|| String::from(ROOT_USER));
+        self.create(parent, name, kind, &owner)
+    }
+
+    /// Checks that `name` could be added to `parent`
+    fn check_new_child(&self, parent: NodeId, name: &str) -> Result<(), VfsError> {
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(VfsError::InvalidName(String::from(name)));
+        }
+        if !self.get(parent).map_or(false, VfsNode::is_dir) {
+            return Err(VfsError::NotADirectory(self.path_of(parent)));
+        }
+        if self.child(parent, name).is_some() {
+            return Err(VfsError::AlreadyExists(self.path_of(parent).join(name)));
+        }
+        Ok(())
+    }
+
+    /// Bumps the modified time of a node
+    pub fn touch(&mut self, id: NodeId) {
+        if let Some(node) = self.get_mut(id) {
+            node.meta.modified = unix_now();
+        }
+    }
+
+    /// Takes a node out of its parent's child list without freeing it
+    fn detach(&mut self, id: NodeId) {
+        let parent = self.get(id).and_then(|node| node.parent);
+        if let Some(parent) = parent {
+            if let Some(NodeKind::Directory { children }) = self.get_mut(parent).map(|node| &mut node.kind) {
+                children.retain(|child| *child != id);
+            }
+            self.touch(parent);
+        }
+    }
+
+    /// Removes a node and everything under it
+    pub fn remove(&mut self, id: NodeId) -> Result<(), VfsError> {
+        if id == Vfs::ROOT {
+            return Err(VfsError::IsRoot);
+        }
+        self.detach(id);
+
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            stack.extend_from_slice(self.children(current));
+            self.nodes[current.0] = None;
+        }
+        Ok(())
+    }
+
+    /// True if `ancestor` is `id` or one of its parents
+    pub fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(node) = current {
+            if node == ancestor {
+                return true;
+            }
+            current = self.get(node).and_then(|node| node.parent);
+        }
+        false
+    }
+
+    /// Moves a node under a new parent, renaming it on the way
+    pub fn move_node(&mut self, id: NodeId, parent: NodeId, name: &str) -> Result<(), VfsError> {
+        if id == Vfs::ROOT {
+            return Err(VfsError::IsRoot);
+        }
+        if self.is_ancestor(id, parent) {
+            return Err(VfsError::IntoItself(self.path_of(id)));
+        }
+
+        self.check_new_child(parent, name)?;
+
+        self.detach(id);
+        if let Some(node) = self.get_mut(id) {
+            node.name = String::from(name);
+            node.parent = Some(parent);
+        }
+        if let Some(NodeKind::Directory { children }) = self.get_mut(parent).map(|node| &mut node.kind) {
+            children.push(id);
+        }
+        self.touch(id);
+        Ok(())
+    }
+
+    /// Copies a node, and everything under it, to a new parent. The copies belong to `owner`
+    pub fn copy_node(&mut self, id: NodeId, parent: NodeId, name: &str, owner: &str) -> Result<NodeId, VfsError> {
+        if self.is_ancestor(id, parent) {
+            return Err(VfsError::IntoItself(self.path_of(id)));
+        }
+        let node = self.get(id).cloned().ok_or_else(|| VfsError::NotFound(self.path_of(id)))?;
+        let kind = match node.kind {
+            NodeKind::Directory { .. } => NodeKind::Directory { children: vec![] },
+            kind => kind,
+        };
+
+        let copy = self.create(parent, name, kind, owner)?;
+        self.set_permissions(copy, node.meta.permissions);
+        for child in self.children(id).to_vec() {
+            let child_name = self.get(child).map(|node| node.name.clone()).unwrap_or_default();
+            self.copy_node(child, copy, &child_name, owner)?;
+        }
+        Ok(copy)
+    }
+
+    pub fn read_file(&self, id: NodeId) -> Result<&str, VfsError> {
+        match self.get(id).map(|node| &node.kind) {
+            Some(NodeKind::File { content }) => Ok(content),
+            Some(NodeKind::Directory { .. }) => Err(VfsError::IsADirectory(self.path_of(id))),
+            Some(NodeKind::Program) => Err(VfsError::NotAFile(self.path_of(id))),
+            None => Err(VfsError::NotFound(self.path_of(id))),
+        }
+    }
+
+    /// Replaces or appends to a file's content. Appended text goes on a new line
+    pub fn write_file(&mut self, id: NodeId, text: &str, append: bool) -> Result<(), VfsError> {
+        self.read_file(id)?;
+        if let Some(NodeKind::File { content }) = self.get_mut(id).map(|node| &mut node.kind) {
+            if append && !content.is_empty() {
+                content.push('\n');
+                content.push_str(text);
+            } else if append {
+                content.push_str(text);
+            } else {
+                *content = String::from(text);
+            }
+        }
+        self.touch(id);
+        Ok(())
+    }
+
+    /// Writes to the file at `path`, creating it for `owner` if its directory exists
+    pub fn write_path(&mut self, path: &VfsPath, text: &str, append: bool, owner: &str) -> Result<NodeId, VfsError> {
+        let id = match self.lookup(path) {
+            Ok(id) => id,
+            Err(VfsError::NotFound(_)) => {
+                let parent = self.lookup_dir(&path.parent().ok_or(VfsError::IsRoot)?)?;
+                self.create(parent, path.file_name().unwrap_or(""), NodeKind::File { content: String::new() }, owner)?
+            }
+            Err(err) => { return Err(err); }
+        };
+        self.write_file(id, text, append)?;
+        Ok(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(input: &str) -> VfsPath {
+        VfsPath::parse(input, &VfsPath::root(), DEFAULT_USER).unwrap()
+    }
+
+    fn definition(json: &str) -> Directory {
+        parse_definition(json.as_bytes()).unwrap()
+    }
+
+    fn content(fs: &Vfs, at: &str) -> Option<String> {
+        fs.lookup(&path(at)).ok().and_then(|id| fs.read_file(id).ok()).map(String::from)
+    }
+
+    const OLD: &str = r#"{ "name": "root", "children": {
+        "0": { "Directory": { "name": "files", "children": {
+            "0": { "File": { "name": "notes.txt", "content": "first" } },
+            "1": { "File": { "name": "log.txt", "content": "day one" } }
+        } } },
+        "1": { "Directory": { "name": "old", "children": {} } }
+    } }"#;
+
+    #[test]
+    fn parse_resolves_against_the_current_directory() {
+        let cwd = path("/users/sumi");
+        assert_eq!(VfsPath::parse("notes", &cwd, "sumi").unwrap().to_string(), "/users/sumi/notes");
+        assert_eq!(VfsPath::parse("/files/./a.txt", &cwd, "sumi").unwrap().to_string(), "/files/a.txt");
+        assert_eq!(VfsPath::parse("../kai//todo.txt", &cwd, "sumi").unwrap().to_string(), "/users/kai/todo.txt");
+        assert_eq!(VfsPath::parse("../../../..", &cwd, "sumi").unwrap(), VfsPath::root());
+    }
+
+    #[test]
+    fn parse_expands_home_directories() {
+        let cwd = path("/files");
+        assert_eq!(VfsPath::parse("~", &cwd, "sumi").unwrap(), VfsPath::home("sumi"));
+        assert_eq!(VfsPath::parse("~/a.txt", &cwd, "sumi").unwrap().to_string(), "/users/sumi/a.txt");
+        assert_eq!(VfsPath::parse("~kai/todo.txt", &cwd, "sumi").unwrap().to_string(), "/users/kai/todo.txt");
+        assert_eq!(VfsPath::parse("", &cwd, "sumi"), Err(VfsError::EmptyPath));
+    }
+
+    #[test]
+    fn merge_keeps_nodes_the_player_deleted() {
+        let old = definition(OLD);
+        let mut fs = Vfs::from_directory(&old);
+        let notes = fs.lookup(&path("/files/notes.txt")).unwrap();
+        fs.remove(notes).unwrap();
+        let log = fs.lookup(&path("/files/log.txt")).unwrap();
+        let files = fs.lookup(&path("/files")).unwrap();
+        fs.move_node(log, files, "diary.txt").unwrap();
+
+        fs.merge_definition(&old, &old);
+        assert_eq!(content(&fs, "/files/notes.txt"), None);
+        assert_eq!(content(&fs, "/files/log.txt"), None);
+        assert_eq!(content(&fs, "/files/diary.txt").as_deref(), Some("day one"));
+    }
+
+    #[test]
+    fn merge_recreates_deleted_nodes_the_definition_changed() {
+        let old = definition(OLD);
+        let new = definition(&OLD.replace("\"first\"", "\"second\""));
+        let mut fs = Vfs::from_directory(&old);
+        let notes = fs.lookup(&path("/files/notes.txt")).unwrap();
+        fs.remove(notes).unwrap();
+
+        fs.merge_definition(&old, &new);
+        assert_eq!(content(&fs, "/files/notes.txt").as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn merge_applies_only_what_the_definition_changed() {
+        let old = definition(OLD);
+        let new = definition(&OLD
+            .replace("\"first\"", "\"second\"")
+            .replace(r#""1": { "Directory": { "name": "old", "children": {} } }"#,
+                     r#""1": { "File": { "name": "readme.txt", "content": "new" } }"#));
+        let mut fs = Vfs::from_directory(&old);
+        let log = fs.lookup(&path("/files/log.txt")).unwrap();
+        fs.write_file(log, "day two", true).unwrap();
+        fs.write_path(&path("/files/mine.txt"), "player file", false, DEFAULT_USER).unwrap();
+
+        fs.merge_definition(&old, &new);
+        assert_eq!(content(&fs, "/files/notes.txt").as_deref(), Some("second"));
+        assert_eq!(content(&fs, "/files/log.txt").as_deref(), Some("day one\nday two"));
+        assert_eq!(content(&fs, "/files/mine.txt").as_deref(), Some("player file"));
+        assert_eq!(content(&fs, "/readme.txt").as_deref(), Some("new"));
+        assert!(fs.lookup(&path("/old")).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_removed_directories_that_hold_player_files() {
+        let old = definition(OLD);
+        let new = definition(r#"{ "name": "root", "children": {} }"#);
+        let mut fs = Vfs::from_directory(&old);
+        fs.write_path(&path("/files/mine.txt"), "player file", false, DEFAULT_USER).unwrap();
+
+        fs.merge_definition(&old, &new);
+        assert_eq!(content(&fs, "/files/notes.txt"), None);
+        assert_eq!(content(&fs, "/files/mine.txt").as_deref(), Some("player file"));
+        assert!(fs.lookup(&path("/old")).is_err());
+    }
+
+    #[test]
+    fn created_nodes_belong_to_their_creator() {
+        let mut fs = Vfs::from_directory(&definition(OLD));
+        let id = fs.write_path(&path("/files/mine.txt"), "", false, "kai").unwrap();
+        assert_eq!(fs.get(id).unwrap().meta.owner, "kai");
+    }
+
+    #[test]
+    fn locked_directories_hide_what_is_inside() {
+        let mut fs = Vfs::from_directory(&definition(OLD));
+        let files = fs.lookup(&path("/files")).unwrap();
+        let notes = fs.lookup(&path("/files/notes.txt")).unwrap();
+        fs.set_owner(files, "kai");
+        fs.set_permissions(files, Permissions::parse("rwxr--").unwrap());
+
+        assert_eq!(fs.check_access(files, "sumi", Access::READ), Ok(()));
+        assert_eq!(fs.check_access(notes, "sumi", Access::NONE), Err(VfsError::PermissionDenied(path("/files"))));
+        assert_eq!(fs.check_access(notes, "kai", Access::READ), Ok(()));
+        assert_eq!(fs.check_access(notes, ROOT_USER, Access::ALL), Ok(()));
+    }
+
+    #[test]
+    fn saved_trees_keep_their_metadata() {
+        let mut fs = Vfs::from_directory(&definition(OLD));
+        let notes = fs.lookup(&path("/files/notes.txt")).unwrap();
+        let files = fs.lookup(&path("/files")).unwrap();
+        fs.set_owner(notes, "kai");
+        fs.set_permissions(notes, Permissions::parse("rw----").unwrap());
+        for (id, created) in [(notes, 100), (files, 50)] {
+            let meta = &mut fs.get_mut(id).unwrap().meta;
+            meta.created = created;
+            meta.modified = created + 1;
+        }
+
+        let json = serde_json::to_vec(&fs.to_directory()).unwrap();
+        let restored = Vfs::from_directory(&parse_definition(&json).unwrap());
+        let meta = |fs: &Vfs, at: &str| fs.get(fs.lookup(&path(at)).unwrap()).unwrap().meta.clone();
+        for at in ["/files", "/files/notes.txt"] {
+            let (before, after) = (meta(&fs, at), meta(&restored, at));
+            assert_eq!((after.created, after.modified, &after.owner), (before.created, before.modified, &before.owner));
+            assert_eq!(after.permissions.to_string(), before.permissions.to_string());
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use crate::console::{Directory, GameDirectory};
+use crate::vfs::{self, DefinitionError};
+
+
+
+/// Where the terminal filesystem is defined, relative to `assets/`
+const DEFINITION_PATH: &str = "terminal/default.vfs.json";
+
+/// A terminal filesystem as content authors write it, loaded from a `.vfs.json` file
+#[derive(Asset, TypePath)]
+pub struct VfsDefinition {
+    pub root: Directory,
+}
+
+#[derive(Default)]
+pub struct VfsDefinitionLoader;
+
+impl AssetLoader for VfsDefinitionLoader {
+    type Asset = VfsDefinition;
+    type Settings = ();
+    type Error = DefinitionError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<VfsDefinition, DefinitionError>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await.map_err(DefinitionError::Read)?;
+            Ok(VfsDefinition { root: vfs::parse_definition(&bytes)? })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vfs.json"]
+    }
+}
+
+/// Keeps the definition loaded so edits to the file are picked up
+#[derive(Resource)]
+pub struct VfsDefinitionHandle(pub Handle<VfsDefinition>);
+
+/// The definition the game should build a fresh filesystem from, the built in one until the asset has loaded
+pub fn current_definition(definitions: &Assets<VfsDefinition>, handle: &VfsDefinitionHandle) -> Directory {
+    match definitions.get(&handle.0) {
+        Some(definition) => definition.root.clone(),
+        None => vfs::built_in_definition(),
+    }
+}
+
+fn load_vfs_definition(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(VfsDefinitionHandle(asset_server.load(DEFINITION_PATH)));
+}
+
+/// Merges a loaded or edited definition into the live filesystem, keeping what the player made.
+/// A definition that fails to load leaves the filesystem as it was
+fn reload_vfs_definition(
+    mut events: EventReader<AssetEvent<VfsDefinition>>,
+    handle: Option<Res<VfsDefinitionHandle>>,
+    definitions: Res<Assets<VfsDefinition>>,
+    root: Option<ResMut<GameDirectory>>,
+) {
+    let (handle, mut root) = match (handle, root) {
+        (Some(handle), Some(root)) => (handle, root),
+        _ => {
+            events.clear();
+            return;
+        }
+    };
+
+    let reloaded = events.read().any(|event| event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0));
+    if let Some(definition) = definitions.get(&handle.0).filter(|_| reloaded) {
+        info!("reloading the terminal filesystem from {}", DEFINITION_PATH);
+        root.merge_definition(definition.root.clone());
+    }
+}
+
+pub struct VfsAssetPlugin;
+
+impl Plugin for VfsAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<VfsDefinition>()
+            .init_asset_loader::<VfsDefinitionLoader>()
+            .add_systems(Startup, load_vfs_definition)
+            .add_systems(Update, reload_vfs_definition);
+    }
+}